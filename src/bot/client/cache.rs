@@ -0,0 +1,317 @@
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, HashMap};
+use std::collections::hash_map::DefaultHasher;
+use std::fs::{self, File};
+use std::hash::{Hash, Hasher};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use serde_json;
+
+use config::Config;
+
+/// A cached GET response, along with what's needed to revalidate it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Entry {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    /// The `Link` header, so cached pages can still be followed.
+    pub link: Option<String>,
+    #[serde(skip_serializing, default)]
+    pub body: Vec<u8>,
+}
+
+impl Entry {
+    fn size(&self) -> usize {
+        self.body.len()
+            + self.etag.as_ref().map_or(0, |s| s.len())
+            + self.last_modified.as_ref().map_or(0, |s| s.len())
+            + self.link.as_ref().map_or(0, |s| s.len())
+    }
+}
+
+/// Somewhere to keep cached responses, keyed by host and url.
+pub trait Store {
+    fn get(&self, key: &str) -> Option<Entry>;
+    fn put(&self, key: &str, entry: Entry);
+}
+
+/// Keeps responses in memory, evicting the least recently used once the
+/// bodies add up to more than `max_bytes`.
+pub struct MemoryStore {
+    max_bytes: usize,
+    inner: RefCell<Lru>,
+}
+
+struct Lru {
+    // each entry, and when it was last used
+    entries: HashMap<String, (Entry, u64)>,
+    // the keys by when they were last used, oldest first
+    order: BTreeMap<u64, String>,
+    clock: u64,
+    bytes: usize,
+}
+
+impl MemoryStore {
+    pub fn new(max_bytes: usize) -> MemoryStore {
+        MemoryStore {
+            max_bytes: max_bytes,
+            inner: RefCell::new(Lru {
+                entries: HashMap::new(),
+                order: BTreeMap::new(),
+                clock: 0,
+                bytes: 0,
+            }),
+        }
+    }
+}
+
+impl Lru {
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+
+    fn get(&mut self, key: &str) -> Option<Entry> {
+        let now = self.tick();
+        let (entry, used) = match self.entries.get_mut(key) {
+            Some(&mut (ref entry, ref mut used)) => {
+                let last = *used;
+                *used = now;
+                (entry.clone(), last)
+            },
+            None => return None,
+        };
+        self.order.remove(&used);
+        self.order.insert(now, key.to_string());
+        Some(entry)
+    }
+
+    fn remove(&mut self, key: &str) {
+        if let Some((old, used)) = self.entries.remove(key) {
+            self.bytes -= old.size();
+            self.order.remove(&used);
+        }
+    }
+
+    fn remove_oldest(&mut self) -> bool {
+        let oldest = match self.order.values().next() {
+            Some(key) => key.clone(),
+            None => return false,
+        };
+        self.remove(&oldest);
+        true
+    }
+}
+
+impl Store for MemoryStore {
+    fn get(&self, key: &str) -> Option<Entry> {
+        self.inner.borrow_mut().get(key)
+    }
+
+    fn put(&self, key: &str, entry: Entry) {
+        let mut lru = self.inner.borrow_mut();
+        lru.remove(key);
+        let size = entry.size();
+        if size > self.max_bytes {
+            trace!("response too big to cache: {} bytes", size);
+            return;
+        }
+        while lru.bytes + size > self.max_bytes && lru.remove_oldest() {}
+        let now = lru.tick();
+        lru.bytes += size;
+        lru.order.insert(now, key.to_string());
+        lru.entries.insert(key.to_string(), (entry, now));
+    }
+}
+
+/// Keeps responses as files in a directory, so they outlive the process.
+///
+/// Each file is a line of JSON metadata followed by the raw body. Once the
+/// files add up to more than `max_bytes`, the least recently written are
+/// deleted.
+pub struct DiskStore {
+    dir: PathBuf,
+    max_bytes: u64,
+    bytes: Cell<u64>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct DiskMeta {
+    key: String,
+    entry: Entry,
+}
+
+impl DiskStore {
+    pub fn new<P: Into<PathBuf>>(dir: P, max_bytes: u64) -> io::Result<DiskStore> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        let store = DiskStore {
+            dir: dir,
+            max_bytes: max_bytes,
+            bytes: Cell::new(0),
+        };
+        let bytes = store.files()?.iter().map(|&(_, len, _)| len).sum();
+        store.bytes.set(bytes);
+        store.shrink(None)?;
+        Ok(store)
+    }
+
+    // every cached file, with its size and when it was written
+    fn files(&self) -> io::Result<Vec<(PathBuf, u64, SystemTime)>> {
+        let mut files = Vec::new();
+        for dirent in fs::read_dir(&self.dir)? {
+            let dirent = dirent?;
+            let meta = dirent.metadata()?;
+            if meta.is_file() {
+                files.push((dirent.path(), meta.len(), meta.modified()?));
+            }
+        }
+        Ok(files)
+    }
+
+    // deletes the oldest files until they all fit, sparing `keep`
+    fn shrink(&self, keep: Option<&Path>) -> io::Result<()> {
+        if self.bytes.get() <= self.max_bytes {
+            return Ok(());
+        }
+        let mut files = self.files()?;
+        files.sort_by_key(|&(_, _, written)| written);
+        let mut bytes = files.iter().map(|&(_, len, _)| len).sum::<u64>();
+        for (path, len, _) in files {
+            if bytes <= self.max_bytes {
+                break;
+            }
+            if Some(path.as_path()) == keep {
+                continue;
+            }
+            match fs::remove_file(&path) {
+                Ok(()) => bytes -= len,
+                Err(ref e) if e.kind() == io::ErrorKind::NotFound => bytes -= len,
+                Err(e) => warn!("error evicting cached response {:?}: {}", path, e),
+            }
+        }
+        self.bytes.set(bytes);
+        Ok(())
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        self.dir.join(format!("{:016x}", hasher.finish()))
+    }
+
+    fn read(&self, key: &str) -> io::Result<Option<Entry>> {
+        let file = match File::open(self.path(key)) {
+            Ok(file) => file,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let mut reader = BufReader::new(file);
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        let meta: DiskMeta = serde_json::from_str(&line)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        // two urls that hash the same; not ours
+        if meta.key != key {
+            return Ok(None);
+        }
+        let mut entry = meta.entry;
+        reader.read_to_end(&mut entry.body)?;
+        Ok(Some(entry))
+    }
+
+    fn write(&self, key: &str, entry: Entry) -> io::Result<()> {
+        let path = self.path(key);
+        let tmp = path.with_extension("tmp");
+        let replaced = fs::metadata(&path).map(|meta| meta.len()).unwrap_or(0);
+        let written = {
+            let mut file = File::create(&tmp)?;
+            let body = entry.body.clone();
+            let meta = serde_json::to_string(&DiskMeta {
+                key: key.to_string(),
+                entry: entry,
+            }).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            file.write_all(meta.as_bytes())?;
+            file.write_all(b"\n")?;
+            file.write_all(&body)?;
+            meta.len() as u64 + 1 + body.len() as u64
+        };
+        fs::rename(tmp, &path)?;
+        self.bytes.set(self.bytes.get().saturating_sub(replaced) + written);
+        self.shrink(Some(&path))
+    }
+}
+
+impl Store for DiskStore {
+    fn get(&self, key: &str) -> Option<Entry> {
+        match self.read(key) {
+            Ok(entry) => entry,
+            Err(e) => {
+                warn!("error reading cached response for {}: {}", key, e);
+                None
+            }
+        }
+    }
+
+    fn put(&self, key: &str, entry: Entry) {
+        if let Err(e) = self.write(key, entry) {
+            warn!("error caching response for {}: {}", key, e);
+        }
+    }
+}
+
+/// The memory store in front of an optional disk store.
+pub struct Cache {
+    memory: MemoryStore,
+    disk: Option<DiskStore>,
+}
+
+impl Cache {
+    pub fn new(memory: MemoryStore, disk: Option<DiskStore>) -> Cache {
+        Cache {
+            memory: memory,
+            disk: disk,
+        }
+    }
+}
+
+impl Store for Cache {
+    fn get(&self, key: &str) -> Option<Entry> {
+        if let Some(entry) = self.memory.get(key) {
+            return Some(entry);
+        }
+        let entry = self.disk.as_ref().and_then(|disk| disk.get(key));
+        if let Some(ref entry) = entry {
+            self.memory.put(key, entry.clone());
+        }
+        entry
+    }
+
+    fn put(&self, key: &str, entry: Entry) {
+        if let Some(ref disk) = self.disk {
+            disk.put(key, entry.clone());
+        }
+        self.memory.put(key, entry);
+    }
+}
+
+/// The cache the config asks for, if any.
+pub fn from_config(config: &Config) -> Option<Cache> {
+    let max_bytes = config.cache_max_bytes();
+    if max_bytes == 0 {
+        debug!("response cache disabled");
+        return None;
+    }
+    let disk = config.cache_dir().and_then(|dir| {
+        match DiskStore::new(dir, config.cache_max_disk_bytes()) {
+            Ok(store) => Some(store),
+            Err(e) => {
+                warn!("can't use cache dir {:?}, caching in memory only: {}", dir, e);
+                None
+            }
+        }
+    });
+    Some(Cache::new(MemoryStore::new(max_bytes), disk))
+}
@@ -0,0 +1,277 @@
+use std::error::Error as StdError;
+use std::fmt;
+
+use hyper::{self, Method, StatusCode};
+use serde_json;
+
+use super::GraphQlError;
+
+/// An error talking to the GitHub API.
+#[derive(Debug)]
+pub struct Error {
+    kind: ErrorKind,
+    request: Option<String>,
+}
+
+#[derive(Debug)]
+enum ErrorKind {
+    Http(hyper::Error),
+    Api(Api),
+    Json(serde_json::Error),
+    GraphQl(Vec<GraphQlError>),
+}
+
+#[derive(Debug)]
+struct Api {
+    status: StatusCode,
+    body: String,
+    message: Option<String>,
+    documentation_url: Option<String>,
+    errors: Vec<FieldError>,
+    rate_limited: bool,
+}
+
+/// A single entry of the `errors` array GitHub sends with validation
+/// failures.
+#[derive(Clone, Debug, Deserialize)]
+pub struct FieldError {
+    pub resource: Option<String>,
+    pub field: Option<String>,
+    pub code: Option<String>,
+    pub message: Option<String>,
+}
+
+/// The broad reason a request failed, for deciding what to do about it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Category {
+    /// A network problem or a server error; trying again may work.
+    Retryable,
+    /// Our credentials are missing, wrong, or lack permission.
+    Auth,
+    /// The resource doesn't exist, or we aren't allowed to know it does.
+    NotFound,
+    /// GitHub rejected what we sent.
+    Validation,
+    /// We've used up our request budget for now.
+    RateLimited,
+    Other,
+}
+
+#[derive(Deserialize)]
+struct Body {
+    message: Option<String>,
+    documentation_url: Option<String>,
+    #[serde(default)]
+    errors: Vec<FieldError>,
+}
+
+impl Error {
+    pub fn http(e: hyper::Error) -> Error {
+        Error {
+            kind: ErrorKind::Http(e),
+            request: None,
+        }
+    }
+
+    pub fn json(e: serde_json::Error) -> Error {
+        Error {
+            kind: ErrorKind::Json(e),
+            request: None,
+        }
+    }
+
+    pub fn graphql(errors: Vec<GraphQlError>) -> Error {
+        Error {
+            kind: ErrorKind::GraphQl(errors),
+            request: None,
+        }
+    }
+
+    pub fn api(status: StatusCode, body: &[u8], rate_limited: bool) -> Error {
+        let parsed = serde_json::from_slice::<Body>(body).ok();
+        let (message, documentation_url, errors) = match parsed {
+            Some(b) => (b.message, b.documentation_url, b.errors),
+            None => (None, None, Vec::new()),
+        };
+        Error {
+            kind: ErrorKind::Api(Api {
+                status: status,
+                body: String::from_utf8_lossy(body).into_owned(),
+                message: message,
+                documentation_url: documentation_url,
+                errors: errors,
+                rate_limited: rate_limited,
+            }),
+            request: None,
+        }
+    }
+
+    /// Record which request this error came from.
+    pub fn during(mut self, method: &Method, path: &str) -> Error {
+        self.request = Some(format!("{} {}", method, path));
+        self
+    }
+
+    pub fn category(&self) -> Category {
+        let api = match self.kind {
+            ErrorKind::Http(hyper::Error::Uri(_)) => return Category::Other,
+            ErrorKind::Http(_) => return Category::Retryable,
+            ErrorKind::Json(_) => return Category::Other,
+            ErrorKind::GraphQl(ref errors) => return graphql_category(errors),
+            ErrorKind::Api(ref api) => api,
+        };
+        match api.status {
+            StatusCode::TooManyRequests => Category::RateLimited,
+            StatusCode::Forbidden if api.rate_limited => Category::RateLimited,
+            StatusCode::Unauthorized |
+            StatusCode::Forbidden => Category::Auth,
+            StatusCode::NotFound => Category::NotFound,
+            StatusCode::BadRequest |
+            StatusCode::UnprocessableEntity => Category::Validation,
+            s if s.is_server_error() => Category::Retryable,
+            _ => Category::Other,
+        }
+    }
+
+    /// The method and path of the failed request, like `POST /repos/a/b/issues`.
+    pub fn request(&self) -> Option<&str> {
+        self.request.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn status(&self) -> Option<StatusCode> {
+        match self.kind {
+            ErrorKind::Api(ref api) => Some(api.status),
+            _ => None,
+        }
+    }
+
+    /// The raw response body GitHub sent along with the error status.
+    pub fn body(&self) -> Option<&str> {
+        match self.kind {
+            ErrorKind::Api(ref api) => Some(&api.body),
+            _ => None,
+        }
+    }
+
+    /// GitHub's `message` explaining the error.
+    pub fn message(&self) -> Option<&str> {
+        match self.kind {
+            ErrorKind::Api(ref api) => api.message.as_ref().map(AsRef::as_ref),
+            _ => None,
+        }
+    }
+
+    pub fn documentation_url(&self) -> Option<&str> {
+        match self.kind {
+            ErrorKind::Api(ref api) => api.documentation_url.as_ref().map(AsRef::as_ref),
+            _ => None,
+        }
+    }
+
+    /// The `errors` of a GraphQL response.
+    pub fn graphql_errors(&self) -> &[GraphQlError] {
+        match self.kind {
+            ErrorKind::GraphQl(ref errors) => errors,
+            _ => &[],
+        }
+    }
+
+    /// The per-field details of a validation failure.
+    pub fn field_errors(&self) -> &[FieldError] {
+        match self.kind {
+            ErrorKind::Api(ref api) => &api.errors,
+            _ => &[],
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(ref request) = self.request {
+            write!(f, "{}: ", request)?;
+        }
+        match self.kind {
+            ErrorKind::Http(ref e) => fmt::Display::fmt(e, f),
+            ErrorKind::Json(ref e) => write!(f, "bad response body: {}", e),
+            ErrorKind::GraphQl(ref errors) => {
+                f.write_str("graphql errors")?;
+                for (i, e) in errors.iter().enumerate() {
+                    let sep = if i == 0 { ": " } else { "; " };
+                    write!(f, "{}{}", sep, e.message)?;
+                }
+                Ok(())
+            },
+            ErrorKind::Api(ref api) => {
+                write!(f, "{}", api.status)?;
+                if let Some(ref message) = api.message {
+                    write!(f, ": {}", message)?;
+                }
+                for e in &api.errors {
+                    match (e.field.as_ref(), e.code.as_ref(), e.message.as_ref()) {
+                        (_, _, Some(message)) => write!(f, "; {}", message)?,
+                        (Some(field), Some(code), None) => write!(f, "; {} {}", field, code)?,
+                        _ => (),
+                    }
+                }
+                if let Some(ref url) = api.documentation_url {
+                    write!(f, " (see {})", url)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl StdError for Error {
+    fn description(&self) -> &str {
+        match self.kind {
+            ErrorKind::Http(ref e) => e.description(),
+            ErrorKind::Json(ref e) => e.description(),
+            ErrorKind::GraphQl(_) => "the GitHub GraphQL API returned errors",
+            ErrorKind::Api(_) => "unexpected response from the GitHub API",
+        }
+    }
+
+    fn cause(&self) -> Option<&StdError> {
+        match self.kind {
+            ErrorKind::Http(ref e) => Some(e),
+            ErrorKind::Json(ref e) => Some(e),
+            ErrorKind::GraphQl(_) |
+            ErrorKind::Api(_) => None,
+        }
+    }
+}
+
+impl From<hyper::Error> for Error {
+    fn from(e: hyper::Error) -> Error {
+        Error::http(e)
+    }
+}
+
+// GraphQL requests answer 200 even when they fail, with a `type` in each error
+fn graphql_category(errors: &[GraphQlError]) -> Category {
+    let kind = errors.iter()
+        .filter_map(|e| e.kind.as_ref())
+        .next()
+        .map(AsRef::as_ref);
+    match kind {
+        Some("RATE_LIMITED") => Category::RateLimited,
+        Some("NOT_FOUND") => Category::NotFound,
+        Some("FORBIDDEN") => Category::Auth,
+        Some("INTERNAL") => Category::Retryable,
+        _ => Category::Validation,
+    }
+}
+
+impl fmt::Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            Category::Retryable => "retryable",
+            Category::Auth => "auth failure",
+            Category::NotFound => "not found",
+            Category::Validation => "validation",
+            Category::RateLimited => "rate limited",
+            Category::Other => "other",
+        })
+    }
+}
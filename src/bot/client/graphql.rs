@@ -0,0 +1,59 @@
+use hyper::Method;
+use serde::{Deserialize, Serialize};
+use serde_json::{self, Value};
+
+use super::{Client, Error, Response};
+
+/// One entry of the `errors` array in a GraphQL response.
+#[derive(Clone, Debug, Deserialize)]
+pub struct GraphQlError {
+    pub message: String,
+    #[serde(rename = "type")]
+    pub kind: Option<String>,
+    #[serde(default)]
+    pub path: Vec<Value>,
+    #[serde(default)]
+    pub locations: Vec<Location>,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub struct Location {
+    pub line: u64,
+    pub column: u64,
+}
+
+#[derive(Deserialize)]
+struct Body {
+    data: Option<Value>,
+    #[serde(default)]
+    errors: Vec<GraphQlError>,
+}
+
+impl Client {
+    /// Runs a query against the GraphQL v4 API.
+    ///
+    /// `variables` are serialized as the query's `variables` object, and the
+    /// `data` in the response is deserialized into `T`. Any entries in the
+    /// response's `errors` make this fail, even if partial data came back.
+    pub fn graphql<V, T>(&self, query: &str, variables: &V) -> Response<T>
+    where V: Serialize, T: Deserialize + 'static {
+        let body = json!({
+            "query": query,
+            "variables": variables,
+        }).to_string();
+        let url = self.graphql_url();
+
+        let res = self.send(Method::Post, url.clone(), Some(body))
+            .and_then(move |reply| {
+                let body = reply.json::<Body>()?;
+                if !body.errors.is_empty() {
+                    let e = Error::graphql(body.errors).during(&Method::Post, &url);
+                    debug!("graphql query failed ({}): {}", e.category(), e);
+                    return Err(e);
+                }
+                serde_json::from_value(body.data.unwrap_or(Value::Null))
+                    .map_err(|e| Error::json(e).during(&Method::Post, &url))
+            });
+        Box::new(res)
+    }
+}
@@ -0,0 +1,91 @@
+use hyper::Method;
+
+use super::{segment, Client, Comment, EditIssue, Issue, Label, NewIssue, Pages, Reaction, ReactionKind, Response};
+
+impl Client {
+    pub fn issue(&self, repo: &str, number: u64) -> Response<Issue> {
+        self.get(format!("/repos/{}/issues/{}", repo, number))
+    }
+
+    /// Every open issue and pull request in a repo.
+    pub fn issues(&self, repo: &str) -> Pages<Issue> {
+        Pages::new(self.clone(), format!("/repos/{}/issues", repo))
+    }
+
+    pub fn create_issue(&self, repo: &str, issue: &NewIssue) -> Response<Issue> {
+        self.post(format!("/repos/{}/issues", repo), issue)
+    }
+
+    pub fn edit_issue(&self, repo: &str, number: u64, edit: &EditIssue) -> Response<Issue> {
+        self.patch(format!("/repos/{}/issues/{}", repo, number), edit)
+    }
+
+    /// Every label defined in a repo.
+    pub fn repo_labels(&self, repo: &str) -> Pages<Label> {
+        Pages::new(self.clone(), format!("/repos/{}/labels", repo))
+    }
+
+    pub fn labels(&self, repo: &str, issue: u64) -> Pages<Label> {
+        Pages::new(self.clone(), format!("/repos/{}/issues/{}/labels", repo, issue))
+    }
+
+    /// Adds labels to an issue, returning all of its labels afterwards.
+    pub fn add_labels(&self, repo: &str, issue: u64, labels: &[&str]) -> Response<Vec<Label>> {
+        self.post(format!("/repos/{}/issues/{}/labels", repo, issue), &labels)
+    }
+
+    pub fn remove_label(&self, repo: &str, issue: u64, label: &str) -> Response<()> {
+        self.delete(format!("/repos/{}/issues/{}/labels/{}", repo, issue, segment(label)))
+    }
+
+    pub fn add_assignees(&self, repo: &str, issue: u64, logins: &[&str]) -> Response<Issue> {
+        self.post(format!("/repos/{}/issues/{}/assignees", repo, issue), &json!({
+            "assignees": logins,
+        }))
+    }
+
+    pub fn remove_assignees(&self, repo: &str, issue: u64, logins: &[&str]) -> Response<Issue> {
+        self.send_json(Method::Delete, format!("/repos/{}/issues/{}/assignees", repo, issue), &json!({
+            "assignees": logins,
+        }))
+    }
+
+    /// Comments on an issue, like `github_comment`, but hands back the
+    /// comment so it can be edited later.
+    pub fn create_comment(&self, repo: &str, issue: u64, body: &str) -> Response<Comment> {
+        self.post(format!("/repos/{}/issues/{}/comments", repo, issue), &json!({
+            "body": body,
+        }))
+    }
+
+    /// Replaces the body of an existing comment.
+    pub fn edit_comment(&self, repo: &str, comment: u64, body: &str) -> Response<Comment> {
+        self.patch(format!("/repos/{}/issues/comments/{}", repo, comment), &json!({
+            "body": body,
+        }))
+    }
+
+    pub fn issue_reactions(&self, repo: &str, issue: u64) -> Pages<Reaction> {
+        Pages::new(self.clone(), format!("/repos/{}/issues/{}/reactions", repo, issue))
+    }
+
+    pub fn react_to_issue(&self, repo: &str, issue: u64, kind: ReactionKind) -> Response<Reaction> {
+        self.post(format!("/repos/{}/issues/{}/reactions", repo, issue), &json!({
+            "content": kind,
+        }))
+    }
+
+    pub fn comment_reactions(&self, repo: &str, comment: u64) -> Pages<Reaction> {
+        Pages::new(self.clone(), format!("/repos/{}/issues/comments/{}/reactions", repo, comment))
+    }
+
+    pub fn react_to_comment(&self, repo: &str, comment: u64, kind: ReactionKind) -> Response<Reaction> {
+        self.post(format!("/repos/{}/issues/comments/{}/reactions", repo, comment), &json!({
+            "content": kind,
+        }))
+    }
+
+    pub fn delete_comment_reaction(&self, repo: &str, comment: u64, reaction: u64) -> Response<()> {
+        self.delete(format!("/repos/{}/issues/comments/{}/reactions/{}", repo, comment, reaction))
+    }
+}
@@ -0,0 +1,470 @@
+use std::cell::RefCell;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::rc::Rc;
+use std::time::{Duration, SystemTime};
+
+use futures::{Future, Stream, future};
+use hyper::client::{Client as HyperClient, Request};
+use hyper::{self, Method, StatusCode};
+use hyper::header::{Authorization, Bearer, Headers, UserAgent};
+use serde::{Deserialize, Serialize};
+use serde_json;
+use tokio_core::reactor::{Handle, Timeout};
+
+use config::{self, Config, Github};
+
+pub use self::cache::{Cache, DiskStore, Entry, MemoryStore, Store};
+pub use self::connector::{Connector, Stream};
+pub use self::error::{Category, Error, FieldError};
+pub use self::graphql::{GraphQlError, Location};
+pub use self::models::*;
+pub use self::page::Pages;
+pub use self::rate_limit::{RateLimit, backoff};
+
+mod cache;
+mod connector;
+mod error;
+mod graphql;
+mod issues;
+mod models;
+mod page;
+mod pulls;
+mod rate_limit;
+mod repos;
+
+// the REST v3 media type, plus the previews needed for reactions and checks
+const ACCEPT: &'static str = "application/vnd.github.v3+json, \
+                              application/vnd.github.squirrel-girl-preview+json, \
+                              application/vnd.github.antiope-preview+json";
+
+// including the first try
+const MAX_ATTEMPTS: u32 = 5;
+
+#[derive(Clone)]
+pub struct Client {
+    client: HyperClient<Connector>,
+    config: Config,
+    handle: Handle,
+    cache: Option<Rc<Cache>>,
+    hosts: Rc<Vec<Rc<Host>>>,
+    host: Rc<Host>,
+}
+
+/// What we track per GitHub host.
+struct Host {
+    name: String,
+    rate_limit: RefCell<RateLimit>,
+}
+
+
+pub type Response<T> = Box<Future<Item=T, Error=Error>>;
+
+/// Everything needed to send, and resend, a request.
+#[derive(Clone)]
+struct Outgoing {
+    method: Method,
+    url: String,
+    body: Option<String>,
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+/// A fully read response from the GitHub API.
+struct Reply {
+    status: StatusCode,
+    headers: Headers,
+    body: Vec<u8>,
+}
+
+impl Reply {
+    fn cached(entry: Entry) -> Reply {
+        let mut headers = Headers::new();
+        if let Some(link) = entry.link {
+            headers.set_raw("Link", link);
+        }
+        Reply {
+            status: StatusCode::Ok,
+            headers: headers,
+            body: entry.body,
+        }
+    }
+
+    fn json<T: Deserialize>(&self) -> Result<T, Error> {
+        serde_json::from_slice(&self.body).map_err(Error::json)
+    }
+}
+
+impl Client {
+    /// Fails if the TLS or proxy settings in the config can't be used.
+    pub fn new(config: Config, handle: &Handle) -> io::Result<Client> {
+        let client = HyperClient::configure()
+                .connector(Connector::new(&config, handle)?)
+                .build(handle);
+        let cache = cache::from_config(&config);
+        let mut hosts = config.github_hosts().iter()
+            .map(|g| Rc::new(Host::new(g.name())))
+            .collect::<Vec<_>>();
+        if hosts.is_empty() {
+            // nothing configured, so it's anonymous requests to github.com
+            hosts.push(Rc::new(Host::new("github")));
+        }
+        let host = hosts[0].clone();
+        Ok(Client {
+            client: client,
+            config: config,
+            handle: handle.clone(),
+            cache: cache.map(Rc::new),
+            hosts: Rc::new(hosts),
+            host: host,
+        })
+    }
+
+    /// The config the client was made from.
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// A client for another configured host, sharing this one's connections.
+    ///
+    /// `Client::new` starts out talking to the first host in the config.
+    pub fn host(&self, name: &str) -> Option<Client> {
+        self.hosts.iter().find(|h| h.name == name).map(|host| {
+            let mut client = self.clone();
+            client.host = host.clone();
+            client
+        })
+    }
+
+    /// The name of the host this client talks to.
+    pub fn host_name(&self) -> &str {
+        &self.host.name
+    }
+
+    /// The request budget as of the last response from this host.
+    pub fn rate_limit(&self) -> RateLimit {
+        *self.host.rate_limit.borrow()
+    }
+
+    pub fn github_comment(&self, repo: String, issue: u64, body: String) -> Response<()> {
+        let path = format!("/repos/{}/issues/{}/comments", repo, issue);
+        let body = json!({
+            "body": body
+        }).to_string();
+
+        let res = self.send(Method::Post, path, Some(body))
+            .map(|_| ());
+        Box::new(res)
+    }
+
+    fn get<T: Deserialize + 'static>(&self, path: String) -> Response<T> {
+        Box::new(self.send(Method::Get, path, None).and_then(|reply| reply.json()))
+    }
+
+    fn post<B: Serialize, T: Deserialize + 'static>(&self, path: String, body: &B) -> Response<T> {
+        self.send_json(Method::Post, path, body)
+    }
+
+    fn patch<B: Serialize, T: Deserialize + 'static>(&self, path: String, body: &B) -> Response<T> {
+        self.send_json(Method::Patch, path, body)
+    }
+
+    fn delete(&self, path: String) -> Response<()> {
+        Box::new(self.send(Method::Delete, path, None).map(|_| ()))
+    }
+
+    fn send_json<B: Serialize, T: Deserialize + 'static>(&self, method: Method, path: String, body: &B) -> Response<T> {
+        let body = match serde_json::to_string(body) {
+            Ok(body) => body,
+            Err(e) => return Box::new(future::err(Error::json(e).during(&method, &path))),
+        };
+        Box::new(self.send(method, path, Some(body)).and_then(|reply| reply.json()))
+    }
+
+    /// Sends a request, with retries, and turns any non-success status into
+    /// an `Error` that remembers which request failed.
+    ///
+    /// GETs are revalidated against the cache when there is one, and a
+    /// `304 Not Modified` is answered with the cached body.
+    fn send(&self, method: Method, path: String, body: Option<String>) -> Response<Reply> {
+        let url = self.url(&path);
+        let cached = match (&method, self.cache.as_ref()) {
+            (&Method::Get, Some(cache)) => cache.get(&self.cache_key(&url)),
+            _ => None,
+        };
+        let out = Outgoing {
+            method: method,
+            url: url,
+            body: body,
+            etag: cached.as_ref().and_then(|e| e.etag.clone()),
+            last_modified: cached.as_ref().and_then(|e| e.last_modified.clone()),
+        };
+
+        let this = self.clone();
+        let res = self.attempt(out.clone(), 0)
+            .then(move |result| {
+                let e = match result {
+                    Ok(reply) => {
+                        if reply.status == StatusCode::NotModified {
+                            if let Some(entry) = cached {
+                                trace!("{} not modified, using cached response", out.url);
+                                return Ok(Reply::cached(entry));
+                            }
+                        }
+                        if reply.status.is_success() {
+                            this.store(&out, &reply);
+                            return Ok(reply);
+                        }
+                        let limited = this.host.rate_limit.borrow().is_exhausted();
+                        Error::api(reply.status, &reply.body, limited)
+                    },
+                    Err(e) => e,
+                };
+                let e = e.during(&out.method, &path);
+                debug!("github request failed ({}): {}", e.category(), e);
+                Err(e)
+            });
+        Box::new(res)
+    }
+
+    fn store(&self, out: &Outgoing, reply: &Reply) {
+        if out.method != Method::Get {
+            return;
+        }
+        let cache = match self.cache {
+            Some(ref cache) => cache,
+            None => return,
+        };
+        let entry = Entry {
+            etag: header_string(&reply.headers, "ETag"),
+            last_modified: header_string(&reply.headers, "Last-Modified"),
+            link: header_string(&reply.headers, "Link"),
+            body: reply.body.clone(),
+        };
+        if entry.etag.is_some() || entry.last_modified.is_some() {
+            cache.put(&self.cache_key(&out.url), entry);
+        }
+    }
+
+    // Who asked matters as much as what for: another host's token may see
+    // private repos this one can't. The token is hashed, since keys are
+    // written to disk.
+    fn cache_key(&self, url: &str) -> String {
+        let mut hasher = DefaultHasher::new();
+        self.github().and_then(Github::token).hash(&mut hasher);
+        format!("{} {:016x} {}", self.host.name, hasher.finish(), url)
+    }
+
+    fn attempt(&self, out: Outgoing, attempt: u32) -> Response<Reply> {
+        let this = self.clone();
+        let res = self.wait_for_budget().and_then(move |()| -> Response<Reply> {
+            let req = match this.request(&out) {
+                Ok(req) => req,
+                Err(e) => {
+                    error!("failed to parse uri: {}", e);
+                    return Box::new(future::err(Error::http(hyper::Error::Uri(e))));
+                }
+            };
+
+            this.host.rate_limit.borrow_mut().reserve();
+            let sent = this.client.request(req).and_then(|res| {
+                let status = res.status();
+                let headers = res.headers().clone();
+                res.body().fold(Vec::new(), |mut body, chunk| {
+                    body.extend_from_slice(&chunk);
+                    Ok::<_, hyper::Error>(body)
+                }).map(move |body| Reply {
+                    status: status,
+                    headers: headers,
+                    body: body,
+                })
+            });
+
+            Box::new(sent.then(move |result| -> Response<Reply> {
+                let retry = attempt + 1 < MAX_ATTEMPTS;
+                match result {
+                    Ok(reply) => {
+                        this.host.rate_limit.borrow_mut().update(&reply.headers, SystemTime::now());
+                        if retry && this.should_retry(&out, &reply) {
+                            warn!("{} {} responded {}, retrying", out.method, out.url, reply.status);
+                            this.retry(out, attempt)
+                        } else {
+                            Box::new(future::ok(reply))
+                        }
+                    },
+                    Err(e) => {
+                        if retry && (is_idempotent(&out.method) || never_sent(&e)) {
+                            warn!("{} {} failed, retrying: {}", out.method, out.url, e);
+                            this.retry(out, attempt)
+                        } else {
+                            Box::new(future::err(Error::http(e)))
+                        }
+                    }
+                }
+            }))
+        });
+        Box::new(res)
+    }
+
+    fn retry(&self, out: Outgoing, attempt: u32) -> Response<Reply> {
+        let this = self.clone();
+        let delay = rate_limit::backoff(attempt);
+        debug!("backing off for {:?} before attempt {}", delay, attempt + 2);
+        Box::new(self.sleep(delay).and_then(move |()| {
+            this.attempt(out, attempt + 1)
+        }))
+    }
+
+    fn should_retry(&self, out: &Outgoing, reply: &Reply) -> bool {
+        // GitHub may have acted on a POST before it failed, and sending it
+        // again would do it twice
+        if reply.status.is_server_error() {
+            return is_idempotent(&out.method);
+        }
+        // secondary rate limits are a 403 with a Retry-After, primary ones
+        // are a 403 with nothing remaining; either way nothing was done
+        match reply.status {
+            StatusCode::Forbidden |
+            StatusCode::TooManyRequests => self.host.rate_limit.borrow().is_exhausted(),
+            _ => false,
+        }
+    }
+
+    fn wait_for_budget(&self) -> Response<()> {
+        match self.host.rate_limit.borrow().delay(SystemTime::now()) {
+            Some(delay) => {
+                warn!("rate limit exhausted, delaying request for {:?}", delay);
+                self.sleep(delay)
+            },
+            None => Box::new(future::ok(())),
+        }
+    }
+
+    fn sleep(&self, duration: Duration) -> Response<()> {
+        match Timeout::new(duration, &self.handle) {
+            Ok(timeout) => Box::new(timeout.map_err(|e| Error::http(hyper::Error::Io(e)))),
+            Err(e) => Box::new(future::err(Error::http(hyper::Error::Io(e)))),
+        }
+    }
+
+    // pagination hands us full urls, everything else is a path
+    fn url(&self, path: &str) -> String {
+        if path.starts_with("https://") || path.starts_with("http://") {
+            path.to_string()
+        } else {
+            format!("{}{}", self.api(), path)
+        }
+    }
+
+    fn github(&self) -> Option<&Github> {
+        self.config.github_host(&self.host.name)
+    }
+
+    fn api(&self) -> &str {
+        self.github().map(Github::api).unwrap_or(config::DEFAULT_GITHUB_API)
+    }
+
+    fn graphql_url(&self) -> String {
+        self.github()
+            .map(Github::graphql)
+            .unwrap_or_else(|| config::graphql_url(config::DEFAULT_GITHUB_API))
+    }
+
+    fn request(&self, out: &Outgoing) -> Result<Request, hyper::error::UriError> {
+        let mut req = Request::new(out.method.clone(), out.url.parse()?);
+
+        req.headers_mut().set(UserAgent("fxabot/0".to_string()));
+        req.headers_mut().set_raw("Accept", ACCEPT);
+        if let Some(token) = self.github().and_then(Github::token) {
+            req.headers_mut().set(Authorization(Bearer {
+                token: token.to_string(),
+            }));
+        }
+        if let Some(ref etag) = out.etag {
+            req.headers_mut().set_raw("If-None-Match", etag.clone());
+        }
+        if let Some(ref date) = out.last_modified {
+            req.headers_mut().set_raw("If-Modified-Since", date.clone());
+        }
+        if let Some(ref body) = out.body {
+            req.set_body(body.clone());
+        }
+
+        Ok(req)
+    }
+}
+
+impl Host {
+    fn new(name: &str) -> Host {
+        Host {
+            name: name.to_string(),
+            rate_limit: RefCell::new(RateLimit::default()),
+        }
+    }
+}
+
+// Percent-encodes one path segment, like a label name.
+fn segment(s: &str) -> String {
+    encode(s, false)
+}
+
+// Percent-encodes a path that may have slashes in it, like a file in a
+// repo or `heads/feature`.
+fn path_segments(s: &str) -> String {
+    encode(s, true)
+}
+
+fn encode(s: &str, keep_slashes: bool) -> String {
+    let mut encoded = String::with_capacity(s.len());
+    for byte in s.bytes() {
+        match byte {
+            b'A'...b'Z' | b'a'...b'z' | b'0'...b'9' | b'-' | b'.' | b'_' | b'~' => encoded.push(byte as char),
+            b'/' if keep_slashes => encoded.push('/'),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+// whether sending it twice does no more than sending it once
+fn is_idempotent(method: &Method) -> bool {
+    match *method {
+        Method::Get | Method::Head | Method::Options | Method::Put | Method::Delete => true,
+        _ => false,
+    }
+}
+
+// whether the request failed before any of it reached GitHub
+fn never_sent(e: &hyper::Error) -> bool {
+    match *e {
+        hyper::Error::Io(ref e) => match e.kind() {
+            io::ErrorKind::ConnectionRefused |
+            io::ErrorKind::AddrNotAvailable |
+            io::ErrorKind::NotConnected => true,
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+fn header_string(headers: &Headers, name: &str) -> Option<String> {
+    headers.get_raw(name)
+        .and_then(|raw| raw.one())
+        .and_then(|bytes| ::std::str::from_utf8(bytes).ok())
+        .map(|s| s.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{path_segments, segment};
+
+    #[test]
+    fn test_encode() {
+        assert_eq!(segment("needs review"), "needs%20review");
+        assert_eq!(segment("a/b?c#d%"), "a%2Fb%3Fc%23d%25");
+        assert_eq!(segment("v1.0_rc-1~"), "v1.0_rc-1~");
+        assert_eq!(segment("ü"), "%C3%BC");
+        assert_eq!(path_segments("heads/my feature"), "heads/my%20feature");
+    }
+}
@@ -0,0 +1,254 @@
+//! The shapes of what the GitHub REST API sends and accepts.
+//!
+//! Only the fields the bot has a use for are included; serde ignores the
+//! rest.
+
+use base64;
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct User {
+    pub login: String,
+    pub id: u64,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Label {
+    pub name: String,
+    pub color: String,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Issue {
+    pub number: u64,
+    pub title: String,
+    pub body: Option<String>,
+    pub state: String,
+    pub user: User,
+    #[serde(default)]
+    pub labels: Vec<Label>,
+    #[serde(default)]
+    pub assignees: Vec<User>,
+    pub html_url: String,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Comment {
+    pub id: u64,
+    pub body: String,
+    pub user: User,
+    pub html_url: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct NewIssue {
+    pub title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub labels: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub assignees: Vec<String>,
+}
+
+/// Changes to an issue; fields left as `None` are untouched.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct EditIssue {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Branch {
+    #[serde(rename = "ref")]
+    pub name: String,
+    pub sha: String,
+    pub label: String,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct PullRequest {
+    pub number: u64,
+    pub title: String,
+    pub body: Option<String>,
+    pub state: String,
+    pub user: User,
+    pub head: Branch,
+    pub base: Branch,
+    #[serde(default)]
+    pub merged: bool,
+    pub mergeable: Option<bool>,
+    pub html_url: String,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Review {
+    pub id: u64,
+    pub user: User,
+    pub body: Option<String>,
+    pub state: String,
+    pub commit_id: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum ReviewEvent {
+    #[serde(rename = "APPROVE")]
+    Approve,
+    #[serde(rename = "REQUEST_CHANGES")]
+    RequestChanges,
+    #[serde(rename = "COMMENT")]
+    Comment,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct NewReview {
+    pub event: ReviewEvent,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commit_id: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Commit {
+    pub sha: String,
+    pub commit: CommitDetails,
+    pub author: Option<User>,
+    pub html_url: String,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct CommitDetails {
+    pub message: String,
+    pub author: GitUser,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct GitUser {
+    pub name: String,
+    pub email: String,
+    pub date: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StatusState {
+    #[serde(rename = "pending")]
+    Pending,
+    #[serde(rename = "success")]
+    Success,
+    #[serde(rename = "failure")]
+    Failure,
+    #[serde(rename = "error")]
+    Error,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Status {
+    pub state: StatusState,
+    pub context: String,
+    pub description: Option<String>,
+    pub target_url: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct CombinedStatus {
+    pub state: StatusState,
+    pub sha: String,
+    pub statuses: Vec<Status>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct NewStatus {
+    pub state: StatusState,
+    pub context: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target_url: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct CheckRun {
+    pub id: u64,
+    pub name: String,
+    pub head_sha: String,
+    pub status: String,
+    pub conclusion: Option<String>,
+    pub html_url: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct CheckRuns {
+    pub total_count: u64,
+    pub check_runs: Vec<CheckRun>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Content {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub name: String,
+    pub path: String,
+    pub sha: String,
+    pub size: u64,
+    pub encoding: Option<String>,
+    pub content: Option<String>,
+}
+
+impl Content {
+    /// The decoded file contents, if this is a file GitHub sent inline.
+    pub fn bytes(&self) -> Option<Vec<u8>> {
+        match (self.encoding.as_ref().map(AsRef::as_ref), self.content.as_ref()) {
+            (Some("base64"), Some(content)) => {
+                // GitHub wraps the base64 at 60 columns
+                let packed: String = content.split_whitespace().collect();
+                base64::decode(&packed).ok()
+            },
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Ref {
+    #[serde(rename = "ref")]
+    pub name: String,
+    pub object: RefObject,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct RefObject {
+    pub sha: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ReactionKind {
+    #[serde(rename = "+1")]
+    ThumbsUp,
+    #[serde(rename = "-1")]
+    ThumbsDown,
+    #[serde(rename = "laugh")]
+    Laugh,
+    #[serde(rename = "confused")]
+    Confused,
+    #[serde(rename = "heart")]
+    Heart,
+    #[serde(rename = "hooray")]
+    Hooray,
+    #[serde(rename = "rocket")]
+    Rocket,
+    #[serde(rename = "eyes")]
+    Eyes,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Reaction {
+    pub id: u64,
+    pub user: User,
+    pub content: ReactionKind,
+}
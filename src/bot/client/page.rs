@@ -0,0 +1,143 @@
+use std::collections::VecDeque;
+use std::str;
+
+use futures::{Async, Future, Poll, Stream};
+use hyper::Method;
+use hyper::header::Headers;
+use serde::Deserialize;
+use serde_json;
+
+use super::{Client, Error, Response};
+
+/// A stream of every item in a paginated listing, following the
+/// `Link: rel="next"` header until GitHub stops sending one.
+#[must_use = "streams do nothing unless polled"]
+pub struct Pages<T> {
+    client: Client,
+    extract: fn(&[u8]) -> serde_json::Result<Vec<T>>,
+    items: VecDeque<T>,
+    next: Option<String>,
+    pending: Option<Response<(Vec<T>, Option<String>)>>,
+}
+
+impl<T: Deserialize + 'static> Pages<T> {
+    /// Pages where the body is a plain JSON array.
+    pub fn new(client: Client, path: String) -> Pages<T> {
+        Pages::with(client, path, serde_json::from_slice)
+    }
+}
+
+impl<T: 'static> Pages<T> {
+    /// Pages where the items need to be dug out of each body.
+    pub fn with(client: Client, path: String, extract: fn(&[u8]) -> serde_json::Result<Vec<T>>) -> Pages<T> {
+        let sep = if path.contains('?') { '&' } else { '?' };
+        Pages {
+            client: client,
+            extract: extract,
+            items: VecDeque::new(),
+            next: Some(format!("{}{}per_page=100", path, sep)),
+            pending: None,
+        }
+    }
+
+    fn fetch(&self, path: String) -> Response<(Vec<T>, Option<String>)> {
+        let extract = self.extract;
+        let res = self.client.send(Method::Get, path, None)
+            .and_then(move |reply| {
+                let next = next_link(&reply.headers);
+                extract(&reply.body)
+                    .map(|items| (items, next))
+                    .map_err(Error::json)
+            });
+        Box::new(res)
+    }
+}
+
+impl<T: 'static> Stream for Pages<T> {
+    type Item = T;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Option<T>, Error> {
+        loop {
+            if let Some(item) = self.items.pop_front() {
+                return Ok(Async::Ready(Some(item)));
+            }
+
+            if let Some(mut pending) = self.pending.take() {
+                match pending.poll()? {
+                    Async::Ready((items, next)) => {
+                        self.items.extend(items);
+                        self.next = next;
+                        continue;
+                    },
+                    Async::NotReady => {
+                        self.pending = Some(pending);
+                        return Ok(Async::NotReady);
+                    }
+                }
+            }
+
+            match self.next.take() {
+                Some(path) => self.pending = Some(self.fetch(path)),
+                None => return Ok(Async::Ready(None)),
+            }
+        }
+    }
+}
+
+// Link: <https://api.github.com/repositories/1/issues?page=2>; rel="next", <...>; rel="last"
+fn next_link(headers: &Headers) -> Option<String> {
+    let value = match headers.get_raw("Link").and_then(|raw| raw.one()) {
+        Some(bytes) => match str::from_utf8(bytes) {
+            Ok(s) => s,
+            Err(_) => return None,
+        },
+        None => return None,
+    };
+
+    value.split(',')
+        .filter_map(|link| {
+            let mut parts = link.split(';');
+            let url = match parts.next() {
+                Some(url) => url.trim(),
+                None => return None,
+            };
+            let is_next = parts.any(|param| {
+                let param = param.trim();
+                param == "rel=\"next\"" || param == "rel=next"
+            });
+            if is_next && url.starts_with('<') && url.ends_with('>') {
+                Some(url[1..url.len() - 1].to_string())
+            } else {
+                None
+            }
+        })
+        .next()
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::header::Headers;
+
+    use super::next_link;
+
+    fn link(value: &str) -> Option<String> {
+        let mut headers = Headers::new();
+        headers.set_raw("Link", value.to_string());
+        next_link(&headers)
+    }
+
+    #[test]
+    fn test_next_link() {
+        assert_eq!(link("<https://api.github.com/repositories/1/issues?page=2>; rel=\"next\", \
+                         <https://api.github.com/repositories/1/issues?page=5>; rel=\"last\""),
+                   Some("https://api.github.com/repositories/1/issues?page=2".to_string()));
+        // the order of links doesn't matter, nor do quotes
+        assert_eq!(link("<https://a/?page=1>; rel=\"prev\", <https://a/?page=3>; rel=next"),
+                   Some("https://a/?page=3".to_string()));
+        // the last page has no next
+        assert_eq!(link("<https://a/?page=1>; rel=\"first\", <https://a/?page=2>; rel=\"prev\""), None);
+        assert_eq!(link("https://a/?page=2; rel=\"next\""), None);
+        assert_eq!(next_link(&Headers::new()), None);
+    }
+}
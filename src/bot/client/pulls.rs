@@ -0,0 +1,24 @@
+use super::{Client, Commit, NewReview, Pages, PullRequest, Response, Review};
+
+impl Client {
+    pub fn pull_request(&self, repo: &str, number: u64) -> Response<PullRequest> {
+        self.get(format!("/repos/{}/pulls/{}", repo, number))
+    }
+
+    /// Every open pull request in a repo.
+    pub fn pull_requests(&self, repo: &str) -> Pages<PullRequest> {
+        Pages::new(self.clone(), format!("/repos/{}/pulls", repo))
+    }
+
+    pub fn pull_request_commits(&self, repo: &str, number: u64) -> Pages<Commit> {
+        Pages::new(self.clone(), format!("/repos/{}/pulls/{}/commits", repo, number))
+    }
+
+    pub fn reviews(&self, repo: &str, number: u64) -> Pages<Review> {
+        Pages::new(self.clone(), format!("/repos/{}/pulls/{}/reviews", repo, number))
+    }
+
+    pub fn create_review(&self, repo: &str, number: u64, review: &NewReview) -> Response<Review> {
+        self.post(format!("/repos/{}/pulls/{}/reviews", repo, number), review)
+    }
+}
@@ -0,0 +1,109 @@
+use std::cmp;
+use std::str;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use hyper::header::Headers;
+use rand::{self, Rng};
+
+// the longest we'll ever sleep between two retries
+const MAX_BACKOFF_MS: u64 = 30 * 1000;
+const BASE_BACKOFF_MS: u64 = 500;
+
+/// A snapshot of what GitHub has told us about our request budget.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RateLimit {
+    limit: Option<u64>,
+    remaining: Option<u64>,
+    reset: Option<u64>,
+    retry_after: Option<u64>,
+}
+
+impl RateLimit {
+    /// The total number of requests allowed per window, if known.
+    pub fn limit(&self) -> Option<u64> {
+        self.limit
+    }
+
+    /// How many requests are left in the current window, if known.
+    pub fn remaining(&self) -> Option<u64> {
+        self.remaining
+    }
+
+    /// When the current window resets.
+    pub fn reset(&self) -> Option<SystemTime> {
+        self.reset.map(from_unix)
+    }
+
+    /// When GitHub said we may try again, after a `Retry-After`.
+    pub fn retry_after(&self) -> Option<SystemTime> {
+        self.retry_after.map(from_unix)
+    }
+
+    /// Whether the last response told us to stop sending requests.
+    pub fn is_exhausted(&self) -> bool {
+        self.retry_after.is_some() || self.remaining == Some(0)
+    }
+
+    /// How long a new request should wait before being sent.
+    pub fn delay(&self, now: SystemTime) -> Option<Duration> {
+        let now = to_unix(now);
+        let until = match (self.retry_after, self.remaining, self.reset) {
+            (Some(after), _, _) if after > now => after,
+            (_, Some(0), Some(reset)) if reset > now => reset,
+            _ => return None,
+        };
+        Some(Duration::from_secs(until - now))
+    }
+
+    /// Count a request against the remaining budget before it's sent, so
+    /// several queued requests don't all race for the last slot.
+    pub fn reserve(&mut self) {
+        if let Some(ref mut remaining) = self.remaining {
+            *remaining = remaining.saturating_sub(1);
+        }
+    }
+
+    /// Record the budget headers from a response.
+    pub fn update(&mut self, headers: &Headers, now: SystemTime) {
+        if let Some(limit) = header_u64(headers, "X-RateLimit-Limit") {
+            self.limit = Some(limit);
+        }
+        if let Some(remaining) = header_u64(headers, "X-RateLimit-Remaining") {
+            self.remaining = Some(remaining);
+        }
+        if let Some(reset) = header_u64(headers, "X-RateLimit-Reset") {
+            self.reset = Some(reset);
+        }
+        self.retry_after = header_u64(headers, "Retry-After")
+            .map(|secs| to_unix(now) + secs);
+    }
+}
+
+/// How long to sleep before retry number `attempt` (starting at 0).
+///
+/// Exponential, capped, with half of the delay randomized so that a burst of
+/// failures doesn't retry in lockstep.
+pub fn backoff(attempt: u32) -> Duration {
+    let exp = BASE_BACKOFF_MS.saturating_mul(1 << cmp::min(attempt, 16));
+    let ceil = cmp::min(exp, MAX_BACKOFF_MS);
+    let half = ceil / 2;
+    let jitter = rand::thread_rng().gen_range(0, half + 1);
+    Duration::from_millis(half + jitter)
+}
+
+fn header_u64(headers: &Headers, name: &str) -> Option<u64> {
+    headers.get_raw(name)
+        .and_then(|raw| raw.one())
+        .and_then(|bytes| str::from_utf8(bytes).ok())
+        .and_then(|s| s.trim().parse().ok())
+}
+
+fn to_unix(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn from_unix(secs: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(secs)
+}
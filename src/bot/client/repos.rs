@@ -0,0 +1,59 @@
+use serde_json;
+
+use super::{path_segments, segment, CheckRun, CheckRuns, Client, CombinedStatus, Commit, Content, NewStatus, Pages, Ref,
+            Response, Status};
+
+impl Client {
+    /// A commit, by sha or by anything else that names one, like a branch.
+    pub fn commit(&self, repo: &str, reference: &str) -> Response<Commit> {
+        self.get(format!("/repos/{}/commits/{}", repo, path_segments(reference)))
+    }
+
+    pub fn combined_status(&self, repo: &str, reference: &str) -> Response<CombinedStatus> {
+        self.get(format!("/repos/{}/commits/{}/status", repo, path_segments(reference)))
+    }
+
+    pub fn statuses(&self, repo: &str, reference: &str) -> Pages<Status> {
+        Pages::new(self.clone(), format!("/repos/{}/commits/{}/statuses", repo, path_segments(reference)))
+    }
+
+    pub fn create_status(&self, repo: &str, sha: &str, status: &NewStatus) -> Response<Status> {
+        self.post(format!("/repos/{}/statuses/{}", repo, segment(sha)), status)
+    }
+
+    pub fn check_runs(&self, repo: &str, reference: &str) -> Pages<CheckRun> {
+        fn extract(body: &[u8]) -> serde_json::Result<Vec<CheckRun>> {
+            serde_json::from_slice::<CheckRuns>(body).map(|runs| runs.check_runs)
+        }
+        Pages::with(self.clone(), format!("/repos/{}/commits/{}/check-runs", repo, path_segments(reference)), extract)
+    }
+
+    /// A file or directory in a repo, at `reference` or the default branch.
+    pub fn contents(&self, repo: &str, path: &str, reference: Option<&str>) -> Response<Content> {
+        let path = path_segments(path.trim_left_matches('/'));
+        match reference {
+            Some(r) => self.get(format!("/repos/{}/contents/{}?ref={}", repo, path, segment(r))),
+            None => self.get(format!("/repos/{}/contents/{}", repo, path)),
+        }
+    }
+
+    /// A git reference, such as `heads/master` or `tags/v1.0`.
+    pub fn git_ref(&self, repo: &str, reference: &str) -> Response<Ref> {
+        self.get(format!("/repos/{}/git/refs/{}", repo, path_segments(reference)))
+    }
+
+    /// Creates a reference; `reference` is fully qualified, like `refs/heads/feature`.
+    pub fn create_ref(&self, repo: &str, reference: &str, sha: &str) -> Response<Ref> {
+        self.post(format!("/repos/{}/git/refs", repo), &json!({
+            "ref": reference,
+            "sha": sha,
+        }))
+    }
+
+    pub fn update_ref(&self, repo: &str, reference: &str, sha: &str, force: bool) -> Response<Ref> {
+        self.patch(format!("/repos/{}/git/refs/{}", repo, path_segments(reference)), &json!({
+            "sha": sha,
+            "force": force,
+        }))
+    }
+}
@@ -1,7 +1,11 @@
+use std::io;
 use std::net::SocketAddr;
+use std::time::Duration;
 
-use futures::{Future, future};
-use tokio_core::reactor::Core;
+use futures::{Future, Stream, future};
+use futures::sync::mpsc;
+use tokio_core::reactor::{Core, Handle, Timeout};
+use tokio_signal;
 
 use config::Config;
 
@@ -9,13 +13,31 @@ use self::client::Client;
 use self::server::Server;
 use self::work::Queue;
 
-mod client;
+pub mod client;
 mod server;
-mod work;
+pub mod work;
 
 pub struct FxaBot {
-    addr: SocketAddr,
     core: Core,
+    server: Server,
+    work: Queue,
+    grace: Duration,
+    shutdown: Shutdown,
+    shutdown_rx: Option<mpsc::UnboundedReceiver<()>>,
+}
+
+/// Asks a running `FxaBot` to shut down gracefully, just like SIGTERM does.
+/// It can be cloned and sent to other threads.
+#[derive(Clone)]
+pub struct Shutdown {
+    tx: mpsc::UnboundedSender<()>,
+}
+
+impl Shutdown {
+    pub fn shutdown(&self) {
+        // the bot has already stopped if nobody's listening
+        let _ = self.tx.send(());
+    }
 }
 
 impl FxaBot {
@@ -24,27 +46,127 @@ impl FxaBot {
         let core = Core::new().unwrap();
         let handle = core.handle();
         // attach a client
-        let client = Client::new(config.clone(), &handle);
+        let client = match Client::new(config.clone(), &handle) {
+            Ok(client) => client,
+            Err(e) => {
+                error!("failed to create github client: {}", e);
+                return Err(());
+            }
+        };
         // attach a work queue
-        let work = Queue::new(client, &handle);
+        let work = match Queue::new(&config, client, &handle) {
+            Ok(work) => work,
+            Err(e) => {
+                error!("failed to open the job queue: {}", e);
+                return Err(());
+            }
+        };
+        let grace = Duration::from_secs(config.shutdown_grace());
         // attach a server
-        let addr = Server::listen(config, work, &handle).unwrap();
+        let server = Server::listen(config, work.clone(), &handle).unwrap();
 
+        let (tx, rx) = mpsc::unbounded();
         Ok(FxaBot {
             core: core,
-            addr: addr,
+            server: server,
+            work: work,
+            grace: grace,
+            shutdown: Shutdown {
+                tx: tx,
+            },
+            shutdown_rx: Some(rx),
         })
     }
 
+    /// Runs until SIGTERM, SIGINT or a `Shutdown`, then stops taking
+    /// webhooks and gives running jobs the grace period to finish. Jobs that
+    /// don't finish in time are picked back up on the next start.
     pub fn run(mut self) -> Result<(), ()> {
-        self.run_until(future::empty::<(), ()>())
+        let handle = self.core.handle();
+        let requested = self.shutdown_rx.take()
+            .expect("run only once")
+            .into_future()
+            .map(|_| info!("shutdown requested"))
+            .map_err(|_| ());
+        let signalled = on_signal(tokio_signal::ctrl_c(&handle), "SIGINT")
+            .select(on_sigterm(&handle))
+            .map(|_| ())
+            .map_err(|_| ());
+        self.run_until(requested.select(signalled).map(|_| ()).map_err(|_| ()))?;
+        self.drain()
     }
 
     pub fn run_until<F: Future<Item=(), Error=()>>(&mut self, f: F) -> Result<(), ()> {
         self.core.run(f)
     }
 
+    /// A handle that makes `run` shut down.
+    pub fn shutdown_handle(&self) -> Shutdown {
+        self.shutdown.clone()
+    }
+
+    /// The bot's job queue, for registering kinds of task and scheduling
+    /// jobs of one's own.
+    pub fn queue(&self) -> &Queue {
+        &self.work
+    }
+
     pub fn addr(&self) -> &SocketAddr {
-        &self.addr
+        self.server.addr()
     }
+
+    fn drain(&mut self) -> Result<(), ()> {
+        info!("shutting down, giving running jobs {}s to finish", self.grace.as_secs());
+        self.server.stop();
+        let timeout = match Timeout::new(self.grace, &self.core.handle()) {
+            Ok(timeout) => timeout,
+            Err(e) => {
+                error!("couldn't set a shutdown timer: {}", e);
+                return Err(());
+            }
+        };
+        let drained = self.work.drain()
+            .map(|()| true)
+            .select(timeout.map(|()| false).map_err(|_| ()))
+            .map(|(drained, _)| drained)
+            .map_err(|(e, _)| e);
+        if self.core.run(drained)? {
+            info!("all running jobs finished");
+        } else {
+            warn!("grace period is over; {} running jobs will resume after a restart",
+                  self.work.running());
+        }
+        Ok(())
+    }
+}
+
+// Resolves the first time `signal` arrives. If it can't be listened for, it
+// never resolves.
+fn on_signal<S, F>(signal: F, name: &'static str) -> Box<Future<Item=(), Error=()>>
+    where S: Stream<Error=io::Error> + 'static,
+          F: Future<Item=S, Error=io::Error> + 'static,
+{
+    Box::new(signal.flatten_stream().into_future().then(move |result| -> Box<Future<Item=(), Error=()>> {
+        match result {
+            Ok(_) => {
+                info!("got {}", name);
+                Box::new(future::ok(()))
+            },
+            Err((e, _)) => {
+                error!("can't listen for {}: {}", name, e);
+                Box::new(future::empty())
+            }
+        }
+    }))
+}
+
+#[cfg(unix)]
+fn on_sigterm(handle: &Handle) -> Box<Future<Item=(), Error=()>> {
+    use tokio_signal::unix::{Signal, SIGTERM};
+    on_signal(Signal::new(SIGTERM, handle), "SIGTERM")
+}
+
+#[cfg(not(unix))]
+fn on_sigterm(_handle: &Handle) -> Box<Future<Item=(), Error=()>> {
+    Box::new(future::empty())
 }
@@ -3,8 +3,9 @@ use std::net::SocketAddr;
 
 use futures::future::{self, Future};
 use futures::Stream;
+use futures::sync::oneshot;
 use hyper;
-use hyper::Method::{Get, Post};
+use hyper::Method::{Delete, Get, Post};
 use hyper::server::{Http, Request, Response, Service};
 
 use tokio_core::net::TcpListener;
@@ -16,16 +17,19 @@ use bot::work::Queue;
 mod routes;
 
 pub struct Server {
-    _inner: (),
+    addr: SocketAddr,
+    // dropping or firing this closes the listener
+    stop: Option<oneshot::Sender<()>>,
 }
 
 impl Server {
-    pub fn listen(config: Config, work: Queue, handle: &Handle) -> io::Result<SocketAddr> {
+    pub fn listen(config: Config, work: Queue, handle: &Handle) -> io::Result<Server> {
         let listener = TcpListener::bind(&config.server_addr(), handle)?;
         let addr = listener.local_addr()?;
         let http = Http::new();
         let h = handle.clone();
-        handle.spawn(listener.incoming().for_each(move |(socket, addr)| {
+        let (stop, stopped) = oneshot::channel();
+        let accept = listener.incoming().for_each(move |(socket, addr)| {
             http.bind_connection(&h, socket, addr, Handler {
                 config: config.clone(),
                 work: work.clone(),
@@ -34,9 +38,27 @@ impl Server {
         }).map_err(|e| {
             error!("listener error: {}", e);
             ()
+        });
+        handle.spawn(accept.select(stopped.map_err(|_| ())).then(move |_| {
+            debug!("stopped listening to http://{}", addr);
+            Ok(())
         }));
         info!("listening to http://{}", addr);
-        Ok(addr)
+        Ok(Server {
+            addr: addr,
+            stop: Some(stop),
+        })
+    }
+
+    pub fn addr(&self) -> &SocketAddr {
+        &self.addr
+    }
+
+    /// Stops accepting connections. Ones already open are left to finish.
+    pub fn stop(&mut self) {
+        if let Some(stop) = self.stop.take() {
+            let _ = stop.send(());
+        }
     }
 }
 
@@ -54,10 +76,36 @@ impl Service for Handler {
     type Future = HandlerFuture;
 
     fn call(&self, req: Self::Request) -> Self::Future {
+        // each configured github host gets its own webhook route
+        let github = match *req.method() {
+            Post => self.config.github_route(req.path()).map(|g| g.name().to_string()),
+            _ => None,
+        };
+        if let Some(host) = github {
+            return routes::github(self.config.clone(), host, self.work.clone(), req);
+        }
+
         match (req.method(), req.path()) {
             (&Get, "/") => routes::ping(),
-            (&Post, "/github") if !self.config.github_name().is_empty() => {
-                routes::github(self.config.clone(), self.work.clone(), req)
+            (&Get, "/queue") => routes::queue(&self.work),
+            (&Get, "/jobs") => routes::jobs(&self.config, &self.work, &req),
+            (&Get, path) if path.starts_with("/jobs/") => {
+                routes::job(&self.config, &self.work, &req, &path["/jobs/".len()..])
+            },
+            (&Delete, path) if path.starts_with("/jobs/") => {
+                routes::cancel_job(&self.config, &self.work, &req, &path["/jobs/".len()..])
+            },
+            (&Get, "/dead-letters") => routes::dead_letters(&self.config, &self.work, &req),
+            (&Get, path) if path.starts_with("/dead-letters/") => {
+                routes::dead_letter(&self.config, &self.work, &req, &path["/dead-letters/".len()..])
+            },
+            (&Post, path) if path.starts_with("/dead-letters/") && path.ends_with("/replay")
+                && path.len() > "/dead-letters//replay".len() => {
+                let id = &path["/dead-letters/".len()..path.len() - "/replay".len()];
+                routes::replay_dead_letter(&self.config, &self.work, &req, id)
+            },
+            (&Delete, path) if path.starts_with("/dead-letters/") => {
+                routes::discard_dead_letter(&self.config, &self.work, &req, &path["/dead-letters/".len()..])
             },
             _ => future::ok(Response::new().with_status(hyper::NotFound)).boxed()
         }
@@ -0,0 +1,69 @@
+use futures::{Future, IntoFuture};
+use hyper::{self, StatusCode};
+use hyper::server::{Request, Response};
+
+use bot::work::{Queue, ScheduleError};
+use config::Config;
+use super::super::HandlerFuture;
+use super::{check_admin, json_response};
+
+pub fn dead_letters(config: &Config, work: &Queue, req: &Request) -> HandlerFuture {
+    if let Err(res) = check_admin(config, req) {
+        return Ok(res).into_future().boxed();
+    }
+    json_response(&work.dead_letters())
+}
+
+pub fn dead_letter(config: &Config, work: &Queue, req: &Request, id: &str) -> HandlerFuture {
+    if let Err(res) = check_admin(config, req) {
+        return Ok(res).into_future().boxed();
+    }
+    match id.parse().ok().and_then(|id| work.dead_letter(id)) {
+        Some(letter) => json_response(&letter),
+        None => not_found(),
+    }
+}
+
+/// Answers with the id of the new job.
+pub fn replay_dead_letter(config: &Config, work: &Queue, req: &Request, id: &str) -> HandlerFuture {
+    if let Err(res) = check_admin(config, req) {
+        return Ok(res).into_future().boxed();
+    }
+    let id = match id.parse() {
+        Ok(id) => id,
+        Err(_) => return not_found(),
+    };
+    match work.replay(id) {
+        Ok(Some(job)) => json_response(&json!({ "job": job })),
+        Ok(None) => not_found(),
+        Err(ScheduleError::Full(_)) => {
+            Ok(Response::new().with_status(StatusCode::ServiceUnavailable)).into_future().boxed()
+        },
+        Err(ScheduleError::Unavailable(_)) => {
+            Ok(Response::new().with_status(StatusCode::InternalServerError)).into_future().boxed()
+        },
+    }
+}
+
+pub fn discard_dead_letter(config: &Config, work: &Queue, req: &Request, id: &str) -> HandlerFuture {
+    if let Err(res) = check_admin(config, req) {
+        return Ok(res).into_future().boxed();
+    }
+    let id = match id.parse() {
+        Ok(id) => id,
+        Err(_) => return not_found(),
+    };
+    let status = match work.discard(id) {
+        Ok(true) => StatusCode::NoContent,
+        Ok(false) => StatusCode::NotFound,
+        Err(e) => {
+            error!("failed to discard dead letter {}: {}", id, e);
+            StatusCode::InternalServerError
+        }
+    };
+    Ok(Response::new().with_status(status)).into_future().boxed()
+}
+
+fn not_found() -> HandlerFuture {
+    Ok(Response::new().with_status(hyper::NotFound)).into_future().boxed()
+}
@@ -7,30 +7,78 @@ use hyper::header::{Header, Raw};
 use hyper::server::{Request, Response};
 use serde_json;
 
-use config::Config;
-use bot::work::{Job, Queue};
+use commands::{self, Kind};
+use config::{Config, Github};
+use cron;
+use bot::client::ReactionKind;
+use bot::work::{Acknowledge, Job, JobState, Origin, ProgressComment, Queue, React, Shell};
 use super::super::HandlerFuture;
 use super::RouteError;
+use super::mention::{self, Policy};
+use super::suggest::did_you_mean;
 
-pub fn handle(config: Config, work: Queue, req: Request) -> HandlerFuture {
+pub fn handle(config: Config, host: String, work: Queue, req: Request) -> HandlerFuture {
     GithubHandler {
         config: config,
+        host: host,
         work: work,
     }.handle_request(req)
 }
 
 struct GithubHandler {
     config: Config,
+    host: String,
     work: Queue,
 }
 
 impl GithubHandler {
+    fn github(&self) -> &Github {
+        self.config.github_host(&self.host).expect("routed to a configured host")
+    }
+
+    // A job on behalf of whoever left the comment. It waits for nothing, so
+    // a reply isn't held up behind a deploy.
+    fn job_for(&self, event: &CommentEvent) -> Job {
+        let mut job = self.work.new_job();
+        job.set_origin(Origin {
+            host: self.host.clone(),
+            repo: event.repository.full_name.clone(),
+            issue: event.issue.number,
+            comment: event.comment.id,
+            requester: event.sender.login.clone(),
+        });
+        job.keep_going(self.config.queue_continue_on_failure());
+        job
+    }
+
+    // reacting to commands is turned on per repo
+    fn acknowledgement(&self, event: &CommentEvent) -> Option<Acknowledge> {
+        match self.config.repo(&self.host, &event.repository.full_name) {
+            Some(repo) if repo.reactions() => Some(Acknowledge {
+                host: self.host.clone(),
+                repo: event.repository.full_name.clone(),
+                comment: event.comment.id,
+                username: self.github().username().to_string(),
+            }),
+            _ => None,
+        }
+    }
+
+    // Who asked for job `id`, if it was asked for on this host; jobs can only
+    // be cancelled from there.
+    fn job_owner(&self, id: u64) -> Option<String> {
+        self.work.status(id)
+            .and_then(|status| status.origin)
+            .and_then(|origin| if origin.host == self.host { Some(origin.requester) } else { None })
+    }
+
     fn handle_request(self, req: Request) -> HandlerFuture {
         let event = match req.headers().get() {
             Some(event) => *event,
             None => return Ok(Response::new().with_status(hyper::BadRequest)).into_future().boxed(),
         };
         let sig = req.headers().get::<XHubSignature>().map(|h| h.clone());
+        let retry_after = self.config.queue_retry_after();
 
         let body = Vec::new();
         req.body().fold(body, move |mut body, chunk| {
@@ -40,7 +88,7 @@ impl GithubHandler {
             error!("request body error: {}", err);
             RouteError::Client
         }).and_then(move |body| {
-            if self.config.github_webhook_secret().is_some() {
+            if self.github().webhook_secret().is_some() {
                 if let Some(sig) = sig {
                     self.verify_signature(&body, &sig)?;
                 } else {
@@ -54,17 +102,22 @@ impl GithubHandler {
             match event {
                 XGithubEvent::IssueComment => self.handle_issue_comment(body)
             }
-        }).or_else(|err| {
+        }).or_else(move |err| {
             let status = match err {
                 RouteError::Client => StatusCode::BadRequest,
                 RouteError::Server => StatusCode::InternalServerError,
+                RouteError::Busy => {
+                    let mut res = Response::new().with_status(StatusCode::ServiceUnavailable);
+                    res.headers_mut().set_raw("Retry-After", retry_after.to_string());
+                    return Ok(res);
+                },
             };
             Ok(Response::new().with_status(status))
         }).boxed()
     }
 
     fn verify_signature(&self, body: &[u8], sig: &XHubSignature) -> Result<(), RouteError> {
-        if let Some(secret) = self.config.github_webhook_secret() {
+        if let Some(secret) = self.github().webhook_secret() {
             trace!("verifying signature: {:?}", sig.0);
             let digest = hmac_sha1(secret.as_bytes(), body);
             let digest = format!("{:x}", Hex(&digest));
@@ -99,43 +152,294 @@ impl GithubHandler {
         };
 
         trace!("event: {:?}", event);
-        let cmd = Cmd::parse(&self.config, &event);
-        match cmd {
-            Cmd::Ping => {
-                let mut job = Job::new();
-                job.comment(
-                    event.repository.full_name,
-                    event.issue.number,
-                    format!("@{} pong :ping_pong:", event.sender.login)
-                );
-                self.work.schedule(job).map_err(|_| RouteError::Server)?;
-            },
-            Cmd::Deploy => {
-                let mut job = Job::new();
-                job.comment(
-                    event.repository.full_name,
-                    event.issue.number,
-                    format!("@{} I'd love to... but I don't have that chip installed yet. :sob:", event.sender.login)
-                );
-                self.work.schedule(job).map_err(|_| RouteError::Server)?;
+        let slash = self.config.repo(&self.host, &event.repository.full_name)
+            .map_or(false, |repo| repo.slash_commands());
+        let cmds = Cmd::parse(&self.config, self.github(), slash, &event);
+        if cmds.is_empty() {
+            debug!("ignoring comment");
+            return Ok(Response::new());
+        }
+        if let Some(&Cmd::Unauthorized) = cmds.first() {
+            if self.acknowledgement(&event).is_some() {
+                let mut job = self.job_for(&event);
+                job.push(&React {
+                    host: self.host.clone(),
+                    repo: event.repository.full_name.clone(),
+                    comment: event.comment.id,
+                    reaction: ReactionKind::ThumbsDown,
+                });
+                job.disposable();
+                self.work.schedule(job)?;
+            }
+            return Ok(Response::new());
+        }
 
-            },
-            Cmd::DidNotUnderstand => {
-                // authorized user, but bad command
-                let mut job = Job::new();
-                job.comment(
-                    event.repository.full_name,
-                    event.issue.number,
-                    format!("@{} I'm sorry, I didn't understand you. Bzzt. :zap:", event.sender.login)
-                );
-                self.work.schedule(job).map_err(|_| RouteError::Server)?;
+        let mut job = self.job_for(&event);
+        // nothing runs unless every command makes sense
+        let not_understood = cmds.iter().filter_map(|cmd| match *cmd {
+            Cmd::DidNotUnderstand(ref line) => Some(line.clone()),
+            _ => None,
+        }).collect::<Vec<_>>();
+        if !not_understood.is_empty() {
+            let reply = did_not_understand(&self.config, &not_understood, cmds.len());
+            self.reply(&mut job, &event, &reply);
+            job.disposable();
+            self.work.schedule(job)?;
+            return Ok(Response::new());
+        }
+
+        let ack = self.acknowledgement(&event);
+        let quiet = ack.is_some() && self.config.repo(&self.host, &event.repository.full_name)
+            .map_or(false, |repo| cmds.iter().all(|cmd| repo.quiet(cmd.name())));
+        if let Some(ack) = ack {
+            job.acknowledge(ack);
+        }
+        let mut plan = Plan {
+            replies: Vec::new(),
+            deploys: Vec::new(),
+            cancels: Vec::new(),
+            reminders: Vec::new(),
+        };
+        for cmd in cmds {
+            self.plan_cmd(cmd, &event, &mut plan);
+        }
+
+        // a quiet command's reactions are the whole answer, and a deploy's
+        // progress comment is its own
+        if !quiet && !plan.replies.is_empty() {
+            self.reply(&mut job, &event, &plan.replies.join("\n\n"));
+        }
+        if plan.deploys.is_empty() {
+            // a job that only replies isn't worth replaying if it fails
+            job.disposable();
+        } else {
+            let repo = event.repository.full_name.clone();
+            // one deploy per repo at a time; the second key is named for
+            // what it guards, so any other job that deploys the repo can
+            // wait on it too
+            job.serialize_on(format!("{}/{}", self.host, repo));
+            job.serialize_on(format!("deploy:{}/{}", self.host, repo));
+            job.set_description(format!("deploy of {}", repo));
+            // one comment that follows the deploy along
+            job.report_progress(ProgressComment {
+                host: self.host.clone(),
+                repo: repo,
+                issue: event.issue.number,
+                requester: Some(event.sender.login.clone()),
+                comment: None,
+            });
+            // after the replies, so they don't wait for the deploy
+            for deploy in &plan.deploys {
+                job.push(deploy);
             }
-            Cmd::Ignore => {
-                debug!("ignoring comment");
-            },
+        }
+
+        let mut jobs = vec![job];
+        jobs.extend(plan.reminders);
+        self.work.schedule_all(jobs)?;
+        // only once everything else is sure to happen
+        for id in plan.cancels {
+            self.work.cancel(id);
         }
         Ok(Response::new())
     }
+
+    // Adds `reply` to `job`, addressed to whoever left the comment.
+    fn reply(&self, job: &mut Job, event: &CommentEvent, reply: &str) {
+        // so people can look the job up later
+        let reply = format!("@{} {}\n\n<sub>job {}</sub>", event.sender.login, reply, job.id());
+        job.comment(
+            self.host.clone(),
+            event.repository.full_name.clone(),
+            event.issue.number,
+            reply
+        );
+    }
+
+    // Works out what one command will do and say, without doing any of it
+    // yet.
+    fn plan_cmd(&self, cmd: Cmd, event: &CommentEvent, plan: &mut Plan) {
+        let reply = match cmd {
+            Cmd::Ping => {
+                "pong :ping_pong:".to_string()
+            },
+            Cmd::Deploy(None) => match self.config.command("deploy") {
+                Some(command) => {
+                    plan.deploys.push(Shell::new(command));
+                    return;
+                },
+                None => {
+                    "I'd love to... but I don't have that chip installed yet. :sob:".to_string()
+                },
+            },
+            // `deploy staging` runs the command named `deploy-staging`
+            Cmd::Deploy(Some(target)) => match self.config.command(&format!("deploy-{}", target)) {
+                Some(command) => {
+                    plan.deploys.push(Shell::new(command));
+                    return;
+                },
+                None => {
+                    let targets = self.config.commands().iter()
+                        .map(|command| command.name())
+                        .filter(|name| name.starts_with("deploy-"))
+                        .map(|name| format!("`{}`", &name["deploy-".len()..]))
+                        .collect::<Vec<_>>();
+                    if targets.is_empty() {
+                        format!("I don't know how to deploy to `{}`.", target)
+                    } else {
+                        format!("I don't know how to deploy to `{}`, only to {}.", target, targets.join(", "))
+                    }
+                },
+            },
+            // right after a deploy, it means that deploy, not an older job
+            Cmd::Cancel(None) if !plan.deploys.is_empty() => {
+                plan.deploys.clear();
+                "okay, I won't deploy after all.".to_string()
+            },
+            Cmd::Cancel(id) => {
+                // with no id it means their latest job, so the reply says which
+                let latest = id.is_none();
+                let target = id.or_else(|| self.work.latest_job(&self.host, &event.sender.login));
+                let sender = &event.sender.login;
+                match target.map(|id| (id, self.job_owner(id))) {
+                    // only admins may cancel someone else's job
+                    Some((id, Some(ref owner))) if owner != sender && !self.github().admins().contains(sender) => {
+                        format!("job {} isn't yours to cancel; ask @{}.", id, owner)
+                    },
+                    Some((id, Some(_))) if self.unfinished(id) => {
+                        plan.cancels.push(id);
+                        let what = self.work.status(id).and_then(|status| status.description);
+                        match what {
+                            Some(ref what) if latest => format!("cancelled job {} ({}). :stop_sign:", id, what),
+                            _ => format!("cancelled job {}. :stop_sign:", id),
+                        }
+                    },
+                    Some((id, _)) => {
+                        format!("there's no job {} for me to cancel.", id)
+                    },
+                    None => {
+                        "you don't have any jobs for me to cancel.".to_string()
+                    },
+                }
+            },
+            Cmd::Remind(text) => {
+                let offset = self.config.reminders_utc_offset();
+                match cron::parse_when(&text, cron::now(), offset) {
+                    Some((_, ref message)) if message.is_empty() => {
+                        "what should I remind you about?".to_string()
+                    },
+                    Some((at, message)) => {
+                        let message = strip_prefix(&message, &["to ", "that ", "about "]);
+                        let mut reminder = self.job_for(event);
+                        reminder.run_at(at);
+                        reminder.set_description(format!("reminder: {}", message));
+                        reminder.comment(
+                            self.host.clone(),
+                            event.repository.full_name.clone(),
+                            event.issue.number,
+                            format!("@{} here's your reminder: {} :alarm_clock:", event.sender.login, message)
+                        );
+                        let id = reminder.id();
+                        plan.reminders.push(reminder);
+                        format!("okay, I'll remind you at {}. Say `forget {}` if you change your mind.",
+                                cron::format_local(at, offset), id)
+                    },
+                    None => {
+                        "I couldn't tell when you meant. Try `remind me in 2 days to ...` \
+                         or `remind me tomorrow 9am to ...`.".to_string()
+                    },
+                }
+            },
+            Cmd::Reminders => {
+                let offset = self.config.reminders_utc_offset();
+                let pending = self.work.delayed_jobs(&self.host, &event.sender.login);
+                if pending.is_empty() {
+                    "you don't have any reminders.".to_string()
+                } else {
+                    let mut reply = "here's what I'll remind you about:\n".to_string();
+                    for status in pending {
+                        let at = status.run_at.map(|at| cron::format_local(at, offset)).unwrap_or_default();
+                        let what = status.description.unwrap_or_default();
+                        reply.push_str(&format!("\n- {} at {} ({})", what, at, status.id));
+                    }
+                    reply
+                }
+            },
+            Cmd::Forget(id) => {
+                let pending = self.work.delayed_jobs(&self.host, &event.sender.login)
+                    .iter().any(|status| status.id == id);
+                if pending {
+                    plan.cancels.push(id);
+                    format!("okay, I've forgotten reminder {}.", id)
+                } else {
+                    format!("you don't have a reminder {}.", id)
+                }
+            },
+            Cmd::Help => {
+                let mut help = format!("here's what I can do:\n{}", commands::help());
+                let aliases = self.config.alias_names();
+                if !aliases.is_empty() {
+                    help.push_str("\n\nand some shorthand:\n");
+                    for name in aliases {
+                        help.push_str(&format!("\n- `{}`: `{}`", name, self.config.alias(name).unwrap_or("")));
+                    }
+                }
+                help
+            },
+            // weeded out before anything runs
+            Cmd::DidNotUnderstand(_) | Cmd::Unauthorized => return,
+        };
+        plan.replies.push(reply);
+    }
+
+    // whether job `id` is waiting or running, and so can be cancelled
+    fn unfinished(&self, id: u64) -> bool {
+        self.work.status(id)
+            .map_or(false, |status| status.state == JobState::Queued || status.state == JobState::Running)
+    }
+}
+
+// What a comment's commands come to, worked out before any of it happens so
+// that either all of it does or, if the queue can't take it, none of it.
+struct Plan {
+    replies: Vec<String>,
+    // run after the replies are posted
+    deploys: Vec<Shell>,
+    // jobs to cancel, and reminders to schedule, along with the reply
+    cancels: Vec<u64>,
+    reminders: Vec<Job>,
+}
+
+// authorized user, but bad commands
+fn did_not_understand(config: &Config, lines: &[String], of: usize) -> String {
+    if of == 1 {
+        let hint = lines.first().map(|line| guess(config, line)).unwrap_or_default();
+        return format!("I'm sorry, I didn't understand you. Bzzt. :zap:{} \
+                        Say `help` to see what I can do.", hint);
+    }
+    let mut reply = "I'm sorry, I didn't understand".to_string();
+    for line in lines {
+        reply.push_str(&format!("\n- `{}`{}", line, guess(config, line)));
+    }
+    reply.push_str("\n\nso I didn't do any of it. Bzzt. :zap: Say `help` to see what I can do.");
+    reply
+}
+
+// " Did you mean `deploy`?", if the line starts with a typo of a command
+fn guess(config: &Config, line: &str) -> String {
+    let word = match line.split_whitespace().next() {
+        Some(word) => word,
+        None => return String::new(),
+    };
+    let suggestions = did_you_mean(word, &known_words(config)).into_iter()
+        .filter(|&name| name != word)
+        .map(|name| format!("`{}`", name))
+        .collect::<Vec<_>>();
+    match suggestions.len() {
+        0 => String::new(),
+        1 => format!(" Did you mean {}?", suggestions[0]),
+        n => format!(" Did you mean {} or {}?", suggestions[..n - 1].join(", "), suggestions[n - 1]),
+    }
 }
 
 // The 'X-Github-Event' header
@@ -209,6 +513,7 @@ struct Issue {
 
 #[derive(Debug, Deserialize)]
 struct Comment {
+    id: u64,
     body: String,
     user: User,
 }
@@ -223,63 +528,147 @@ struct User {
     login: String,
 }
 
+// the commands, and the config's aliases for them
+fn known_words(config: &Config) -> Vec<&str> {
+    let mut words = commands::names();
+    words.extend(config.alias_names());
+    words
+}
+
 #[derive(Debug)]
 enum Cmd {
     Ping,
-    Deploy,
-    DidNotUnderstand,
-    Ignore,
+    // where to, or the plain deploy command
+    Deploy(Option<String>),
+    // a job id, or the sender's latest job
+    Cancel(Option<u64>),
+    // when, then what to say
+    Remind(String),
+    Reminders,
+    Forget(u64),
+    Help,
+    // the line that made no sense
+    DidNotUnderstand(String),
+    // someone who isn't authorized mentioned us
+    Unauthorized,
 }
 
 impl Cmd {
-    fn parse(config: &Config, event: &CommentEvent) -> Cmd {
+    // every command in the comment, in order; none if it isn't for us
+    fn parse(config: &Config, github: &Github, slash: bool, event: &CommentEvent) -> Vec<Cmd> {
         if event.action != CommentAction::Created {
-            return Cmd::Ignore;
+            return Vec::new();
         }
-        let my_name = config.github_name();
+        let my_name = github.username();
         if my_name.is_empty() {
-            return Cmd::Ignore;
+            return Vec::new();
         }
-        let authorized = config.github_authorized();
-        if let Some(line) = Cmd::find_mention(&event.comment.body, my_name) {
-            debug!("someone mentioned me: {:?}", line);
-            if authorized.contains(&event.sender.login) {
-                Cmd::parse_line(line)
-            } else {
-                debug!("not someone I trust: {:?}", event.sender.login);
-                Cmd::Ignore
-            }
-        } else {
-            Cmd::Ignore
+        let policy = if github.mentions_anywhere() { Policy::Anywhere } else { Policy::LineStart };
+        let known = known_words(config);
+        let lines = mention::commands(&event.comment.body, my_name, policy, slash).into_iter()
+            // a slash command we don't know is probably another bot's
+            .filter(|line| !line.slash || line.text.split_whitespace().next().map_or(false, |word| known.contains(&word)))
+            .map(|line| line.text)
+            .collect::<Vec<_>>();
+        if lines.is_empty() {
+            return Vec::new();
+        }
+        debug!("someone mentioned me: {:?}", lines);
+        if !github.authorized().contains(&event.sender.login) {
+            debug!("not someone I trust: {:?}", event.sender.login);
+            return vec![Cmd::Unauthorized];
         }
+        lines.iter().map(|line| Cmd::parse_line(config, line)).collect()
+    }
 
+    // what the command is called in the config, as in `quiet = ["ping"]`
+    fn name(&self) -> &'static str {
+        let kind = match *self {
+            Cmd::Ping => Kind::Ping,
+            Cmd::Deploy(_) => Kind::Deploy,
+            Cmd::Cancel(_) => Kind::Cancel,
+            Cmd::Remind(_) => Kind::Remind,
+            Cmd::Reminders => Kind::Reminders,
+            Cmd::Forget(_) => Kind::Forget,
+            Cmd::Help => Kind::Help,
+            Cmd::DidNotUnderstand(_) | Cmd::Unauthorized => return "",
+        };
+        commands::name(kind)
     }
 
-    fn find_mention<'a>(body: &'a str, my_name: &str) -> Option<&'a str> {
-        let min_len = 1 + my_name.len() + 1; // '@my_name '
-        body.split('\n')
-            .skip_while(|line| {
-                !(
-                    line.len() > min_len
-                    && line.starts_with('@')
-                    && line[1..].starts_with(my_name)
-                    && line.as_bytes()[min_len - 1] == b' '
-                )
-            })
-            .next()
+    // what follows the mention or slash, like `deploy` or `remind me ...`,
+    // with an alias standing in for what it's short for
+    fn parse_line(config: &Config, line: &str) -> Cmd {
+        let mut words = line.split_whitespace();
+        let alias = words.next()
+            .and_then(|word| if commands::find(word).is_some() { None } else { config.alias(word) });
+        match alias {
+            Some(command) => {
+                let expanded = Some(command).into_iter().chain(words).collect::<Vec<_>>().join(" ");
+                match Cmd::parse_words(&expanded) {
+                    // complain about what they said, not what it stood for
+                    Cmd::DidNotUnderstand(_) => Cmd::DidNotUnderstand(line.to_string()),
+                    cmd => cmd,
+                }
+            },
+            None => Cmd::parse_words(line),
+        }
     }
 
-    fn parse_line(line: &str) -> Cmd {
-        let mut words = line.split(' ');
-        let _name = words.next();
-        //TODO: assert name is @my_name
+    fn parse_words(line: &str) -> Cmd {
+        let mut words = line.split_whitespace();
+        let kind = match words.next().and_then(commands::find) {
+            // a word too many could mean something else was wanted, like
+            // another environment to deploy to
+            Some(command) if !command.args && words.clone().next().is_some() => {
+                return Cmd::DidNotUnderstand(line.to_string());
+            },
+            Some(command) => command.kind,
+            None => return Cmd::DidNotUnderstand(line.to_string()),
+        };
+        match kind {
+            Kind::Ping => Cmd::Ping,
+            Kind::Deploy => {
+                match (words.next(), words.next()) {
+                    (None, _) => Cmd::Deploy(None),
+                    (Some(target), None) => Cmd::Deploy(Some(target.to_string())),
+                    _ => Cmd::DidNotUnderstand(line.to_string()),
+                }
+            },
+            Kind::Remind => {
+                match words.next() {
+                    Some("me") => Cmd::Remind(words.collect::<Vec<_>>().join(" ")),
+                    _ => Cmd::DidNotUnderstand(line.to_string()),
+                }
+            },
+            Kind::Reminders => Cmd::Reminders,
+            Kind::Help => Cmd::Help,
+            Kind::Forget => {
+                match words.next().and_then(|id| id.trim_left_matches('#').parse().ok()) {
+                    Some(id) => Cmd::Forget(id),
+                    None => Cmd::DidNotUnderstand(line.to_string()),
+                }
+            },
+            Kind::Cancel => {
+                match words.next() {
+                    None => Cmd::Cancel(None),
+                    Some(id) => match id.trim_left_matches('#').parse() {
+                        Ok(id) => Cmd::Cancel(Some(id)),
+                        Err(_) => Cmd::DidNotUnderstand(line.to_string()),
+                    },
+                }
+            },
+        }
+    }
+}
 
-        match words.next() {
-            Some("ping") => Cmd::Ping,
-            Some("deploy") => Cmd::Deploy,
-            _ => Cmd::DidNotUnderstand,
+fn strip_prefix<'a>(s: &'a str, prefixes: &[&str]) -> &'a str {
+    for prefix in prefixes {
+        if s.len() > prefix.len() && s.is_char_boundary(prefix.len()) && s[..prefix.len()].to_lowercase() == *prefix {
+            return &s[prefix.len()..];
         }
     }
+    s
 }
 
 struct Hex<'a>(&'a [u8]);
@@ -0,0 +1,45 @@
+use futures::{Future, IntoFuture};
+use hyper::{self, StatusCode};
+use hyper::server::{Request, Response};
+
+use bot::work::Queue;
+use config::Config;
+use super::super::HandlerFuture;
+use super::{check_admin, json_response};
+
+pub fn jobs(config: &Config, work: &Queue, req: &Request) -> HandlerFuture {
+    if let Err(res) = check_admin(config, req) {
+        return Ok(res).into_future().boxed();
+    }
+    json_response(&work.statuses())
+}
+
+pub fn job(config: &Config, work: &Queue, req: &Request, id: &str) -> HandlerFuture {
+    if let Err(res) = check_admin(config, req) {
+        return Ok(res).into_future().boxed();
+    }
+    match id.parse().ok().and_then(|id| work.status(id)) {
+        Some(status) => json_response(&status),
+        None => Ok(Response::new().with_status(hyper::NotFound)).into_future().boxed(),
+    }
+}
+
+/// Cancelling is asynchronous: a running job stops at its next chance.
+pub fn cancel_job(config: &Config, work: &Queue, req: &Request, id: &str) -> HandlerFuture {
+    if let Err(res) = check_admin(config, req) {
+        return Ok(res).into_future().boxed();
+    }
+    let id = match id.parse() {
+        Ok(id) => id,
+        Err(_) => return Ok(Response::new().with_status(hyper::NotFound)).into_future().boxed(),
+    };
+    let status = if work.cancel(id) {
+        StatusCode::Accepted
+    } else if work.status(id).is_some() {
+        // remembered, but already finished
+        StatusCode::Conflict
+    } else {
+        StatusCode::NotFound
+    };
+    Ok(Response::new().with_status(status)).into_future().boxed()
+}
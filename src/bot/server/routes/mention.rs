@@ -0,0 +1,228 @@
+// Finds where the bot is mentioned in a comment, reading it as Markdown
+// the way GitHub renders it, so mentions in code, quotes and HTML comments
+// don't count.
+
+/// Where a mention has to be to count as a command.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Policy {
+    /// Only at the start of a line, like `@fxabot deploy`.
+    LineStart,
+    /// Anywhere in the prose, like `thanks! @fxabot deploy`.
+    Anywhere,
+}
+
+/// A line that asks something of the bot.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Line {
+    /// What's asked, without the mention or slash, like `deploy`.
+    pub text: String,
+    /// Whether it was a slash command, like `/deploy`, rather than a mention.
+    pub slash: bool,
+}
+
+/// The text following each mention of `@name` in `body`, one per line at
+/// most, in order. The name is matched without regard to case, and may be
+/// followed by a `:` or `,`. With `slash`, lines starting with a `/` count
+/// too.
+pub fn commands(body: &str, name: &str, policy: Policy, slash: bool) -> Vec<Line> {
+    let name = name.to_lowercase();
+    let mut found = Vec::new();
+    let mut fence: Option<(char, usize)> = None;
+    let mut in_comment = false;
+    let mut in_quote = false;
+    let mut in_code = false;
+    let mut after_blank = true;
+
+    for line in body.lines() {
+        if let Some((ch, len)) = fence {
+            if closes_fence(line, ch, len) {
+                fence = None;
+            }
+            continue;
+        }
+        let visible = strip_comments(line, &mut in_comment);
+        if visible.trim().is_empty() {
+            // a blank line ends a quote, but not indented code
+            in_quote = false;
+            after_blank = true;
+            continue;
+        }
+        let blank_before = after_blank;
+        after_blank = false;
+
+        if indent(&visible) >= 4 && (blank_before || in_code) && !in_quote {
+            in_code = true;
+            continue;
+        }
+        in_code = false;
+
+        let text = visible.trim_left();
+        if let Some(opened) = opens_fence(text) {
+            fence = Some(opened);
+            continue;
+        }
+        // a line right after a quoted one carries on the quote, even
+        // without its own `>`
+        if text.starts_with('>') || in_quote {
+            in_quote = true;
+            continue;
+        }
+
+        let prose = strip_code_spans(text);
+        if slash && prose.starts_with('/') && prose[1..].chars().next().map_or(false, char::is_alphanumeric) {
+            found.push(Line {
+                text: prose[1..].trim_right().to_string(),
+                slash: true,
+            });
+        } else if let Some(command) = mention(&prose, &name, policy) {
+            found.push(Line {
+                text: command,
+                slash: false,
+            });
+        }
+    }
+    found
+}
+
+// the command after the first mention in a line of prose
+fn mention(line: &str, name: &str, policy: Policy) -> Option<String> {
+    let mut from = 0;
+    while let Some(at) = line[from..].find('@').map(|i| i + from) {
+        from = at + 1;
+        if policy == Policy::LineStart && at != 0 {
+            return None;
+        }
+        // not part of an email address or another word
+        if line[..at].chars().next_back().map_or(false, is_login_char) {
+            continue;
+        }
+        let rest = &line[at + 1..];
+        if rest.len() < name.len() || !rest.is_char_boundary(name.len()) {
+            continue;
+        }
+        if rest[..name.len()].to_lowercase() != name {
+            continue;
+        }
+        let after = &rest[name.len()..];
+        if after.chars().next().map_or(false, is_login_char) {
+            // a longer name that starts with ours
+            continue;
+        }
+        let command = after.trim_left_matches(|c: char| c == ':' || c == ',' || c.is_whitespace()).trim_right();
+        if command.is_empty() {
+            continue;
+        }
+        return Some(command.to_string());
+    }
+    None
+}
+
+fn is_login_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '-' || c == '_'
+}
+
+// how far in the line starts, with tabs to the next multiple of 4
+fn indent(line: &str) -> usize {
+    let mut width = 0;
+    for c in line.chars() {
+        match c {
+            ' ' => width += 1,
+            '\t' => width += 4 - width % 4,
+            _ => break,
+        }
+    }
+    width
+}
+
+// ``` or ~~~, three or more
+fn opens_fence(text: &str) -> Option<(char, usize)> {
+    let ch = match text.chars().next() {
+        Some(c) if c == '`' || c == '~' => c,
+        _ => return None,
+    };
+    let len = text.chars().take_while(|&c| c == ch).count();
+    if len < 3 {
+        return None;
+    }
+    // backtick fences can't have backticks in their info string
+    if ch == '`' && text[len..].contains('`') {
+        return None;
+    }
+    Some((ch, len))
+}
+
+fn closes_fence(line: &str, ch: char, len: usize) -> bool {
+    if indent(line) >= 4 {
+        return false;
+    }
+    let text = line.trim();
+    let run = text.chars().take_while(|&c| c == ch).count();
+    run >= len && run == text.chars().count()
+}
+
+// drops <!-- comments -->, which may span lines
+fn strip_comments(line: &str, in_comment: &mut bool) -> String {
+    let mut visible = String::new();
+    let mut rest = line;
+    loop {
+        if *in_comment {
+            match rest.find("-->") {
+                Some(end) => {
+                    rest = &rest[end + 3..];
+                    *in_comment = false;
+                },
+                None => return visible,
+            }
+        } else {
+            match rest.find("<!--") {
+                Some(start) => {
+                    visible.push_str(&rest[..start]);
+                    rest = &rest[start + 4..];
+                    *in_comment = true;
+                },
+                None => {
+                    visible.push_str(rest);
+                    return visible;
+                }
+            }
+        }
+    }
+}
+
+// blanks out `code spans`; a span closes with a run of as many backticks
+// as it opened with
+fn strip_code_spans(text: &str) -> String {
+    let mut prose = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find('`') {
+        prose.push_str(&rest[..start]);
+        let run = rest[start..].chars().take_while(|&c| c == '`').count();
+        let after = &rest[start + run..];
+        match find_run(after, run) {
+            Some(end) => {
+                prose.push(' ');
+                rest = &after[end + run..];
+            },
+            None => {
+                // no closing run, so they're just backticks
+                prose.push_str(&rest[start..start + run]);
+                rest = after;
+            }
+        }
+    }
+    prose.push_str(rest);
+    prose
+}
+
+// where a run of exactly `len` backticks starts
+fn find_run(text: &str, len: usize) -> Option<usize> {
+    let mut from = 0;
+    while let Some(start) = text[from..].find('`').map(|i| i + from) {
+        let run = text[start..].chars().take_while(|&c| c == '`').count();
+        if run == len {
+            return Some(start);
+        }
+        from = start + run;
+    }
+    None
+}
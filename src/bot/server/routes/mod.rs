@@ -1,11 +1,91 @@
+use futures::{Future, IntoFuture};
+use hyper::StatusCode;
+use hyper::header::ContentType;
+use hyper::server::{Request, Response};
+use serde::Serialize;
+use serde_json;
+
+use bot::work::ScheduleError;
+use config::Config;
+use super::HandlerFuture;
+
+pub use self::dead_letters::{dead_letter, dead_letters, discard_dead_letter, replay_dead_letter};
 pub use self::github::handle as github;
+pub use self::jobs::{cancel_job, job, jobs};
 pub use self::ping::ping;
+pub use self::queue::queue;
 
+mod dead_letters;
 mod github;
+mod jobs;
+mod mention;
 mod ping;
+mod queue;
+mod suggest;
 
 #[derive(Debug)]
 enum RouteError {
     Client,
     Server,
+    // the queue is full; the sender should try again later
+    Busy,
+}
+
+impl From<ScheduleError> for RouteError {
+    fn from(e: ScheduleError) -> RouteError {
+        match e {
+            ScheduleError::Full(_) => RouteError::Busy,
+            ScheduleError::Unavailable(_) => RouteError::Server,
+        }
+    }
+}
+
+// Admin endpoints want `Authorization: Bearer <admin_token>`. Without a token
+// in the config they don't exist at all.
+fn check_admin(config: &Config, req: &Request) -> Result<(), Response> {
+    let token = match config.admin_token() {
+        Some(token) => token,
+        None => return Err(Response::new().with_status(StatusCode::NotFound)),
+    };
+    let given = req.headers().get_raw("Authorization")
+        .and_then(|raw| raw.one())
+        .and_then(|value| {
+            if value.starts_with(b"Bearer ") {
+                Some(&value[b"Bearer ".len()..])
+            } else {
+                None
+            }
+        });
+    match given {
+        Some(given) if constant_time_eq(given, token.as_bytes()) => Ok(()),
+        _ => {
+            debug!("rejecting admin request to {}", req.path());
+            let mut res = Response::new().with_status(StatusCode::Unauthorized);
+            res.headers_mut().set_raw("WWW-Authenticate", "Bearer");
+            Err(res)
+        }
+    }
+}
+
+// so a wrong token can't be guessed a byte at a time
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn json_response<T: Serialize>(value: &T) -> HandlerFuture {
+    let res = match serde_json::to_string(value) {
+        Ok(body) => {
+            Response::new()
+                .with_header(ContentType::json())
+                .with_body(body)
+        },
+        Err(e) => {
+            error!("error encoding json: {}", e);
+            Response::new().with_status(StatusCode::InternalServerError)
+        }
+    };
+    Ok(res).into_future().boxed()
 }
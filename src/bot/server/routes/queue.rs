@@ -0,0 +1,22 @@
+use futures::{Future, IntoFuture};
+use hyper::header::ContentType;
+use hyper::server::Response;
+
+use bot::work::Queue;
+use super::super::HandlerFuture;
+
+pub fn queue(work: &Queue) -> HandlerFuture {
+    let body = json!({
+        "depth": work.depth(),
+        "capacity": work.capacity(),
+        "delayed": work.delayed(),
+        "max_delayed": work.max_delayed(),
+        "running": work.running(),
+        "waiting": work.waiting(),
+    }).to_string();
+    Ok(
+        Response::new()
+            .with_header(ContentType::json())
+            .with_body(body)
+    ).into_future().boxed()
+}
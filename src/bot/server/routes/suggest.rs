@@ -0,0 +1,77 @@
+// Guesses which command someone meant when they made a typo.
+
+// how many suggestions to make at most
+const MAX_SUGGESTIONS: usize = 3;
+
+/// The known words closest to `word`, closest first, if any are close
+/// enough to be a typo of it.
+pub fn did_you_mean<'a>(word: &str, known: &[&'a str]) -> Vec<&'a str> {
+    let word = word.to_lowercase();
+    // one slip in a short word, two in a longer one
+    let max = if word.chars().count() <= 4 { 1 } else { 2 };
+    let mut close = known.iter()
+        .map(|&candidate| (distance(&word, &candidate.to_lowercase()), candidate))
+        .filter(|&(d, _)| d <= max)
+        .collect::<Vec<_>>();
+    close.sort();
+    close.dedup_by_key(|&mut (_, candidate)| candidate);
+    close.into_iter().take(MAX_SUGGESTIONS).map(|(_, candidate)| candidate).collect()
+}
+
+// Edits to turn `a` into `b`: insertions, deletions, substitutions, and
+// swaps of neighbours, which are the usual typo.
+fn distance(a: &str, b: &str) -> usize {
+    let a = a.chars().collect::<Vec<_>>();
+    let b = b.chars().collect::<Vec<_>>();
+    let width = b.len() + 1;
+    let mut d = vec![0; (a.len() + 1) * width];
+    for i in 0..a.len() + 1 {
+        d[i * width] = i;
+    }
+    for j in 0..b.len() + 1 {
+        d[j] = j;
+    }
+    for i in 1..a.len() + 1 {
+        for j in 1..b.len() + 1 {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            let mut best = (d[(i - 1) * width + j] + 1)
+                .min(d[i * width + j - 1] + 1)
+                .min(d[(i - 1) * width + j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                best = best.min(d[(i - 2) * width + j - 2] + 1);
+            }
+            d[i * width + j] = best;
+        }
+    }
+    d[a.len() * width + b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use commands;
+    use super::did_you_mean;
+
+    #[test]
+    fn test_swapped_letters() {
+        assert_eq!(did_you_mean("deplyo", &commands::names()), vec!["deploy"]);
+        assert_eq!(did_you_mean("pnig", &commands::names()), vec!["ping"]);
+    }
+
+    #[test]
+    fn test_short_words_allow_one_slip() {
+        assert_eq!(did_you_mean("pxxg", &commands::names()), Vec::<&str>::new());
+        assert_eq!(did_you_mean("dploi", &commands::names()), vec!["deploy"]);
+    }
+
+    #[test]
+    fn test_closest_first_then_alphabetical() {
+        assert_eq!(did_you_mean("reminder", &commands::names()), vec!["reminders", "remind"]);
+        let known = ["pint", "pink", "ping", "pine"];
+        assert_eq!(did_you_mean("pin", &known), vec!["pine", "ping", "pink"]);
+    }
+
+    #[test]
+    fn test_case_and_duplicates() {
+        assert_eq!(did_you_mean("PING", &["ping", "ping"]), vec!["ping"]);
+    }
+}
@@ -0,0 +1,64 @@
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+use futures::{Future, Poll};
+use futures::task::{self, Task};
+
+/// Lets a job be stopped partway through. Clones share the same state.
+#[derive(Clone)]
+pub struct CancelToken(Rc<Inner>);
+
+struct Inner {
+    cancelled: Cell<bool>,
+    // whoever is waiting on the task that's running right now
+    waiting: RefCell<Option<Task>>,
+}
+
+impl CancelToken {
+    pub fn new() -> CancelToken {
+        CancelToken(Rc::new(Inner {
+            cancelled: Cell::new(false),
+            waiting: RefCell::new(None),
+        }))
+    }
+
+    pub fn cancel(&self) {
+        self.0.cancelled.set(true);
+        if let Some(task) = self.0.waiting.borrow_mut().take() {
+            task.unpark();
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.cancelled.get()
+    }
+
+    /// Runs `future` until it's done or the token is cancelled, whichever
+    /// comes first. A cancelled future is dropped, which is what stops it.
+    pub fn guard<F: Future>(&self, future: F, cancelled: F::Error) -> Guard<F> {
+        Guard {
+            future: future,
+            token: self.clone(),
+            cancelled: Some(cancelled),
+        }
+    }
+}
+
+pub struct Guard<F: Future> {
+    future: F,
+    token: CancelToken,
+    cancelled: Option<F::Error>,
+}
+
+impl<F: Future> Future for Guard<F> {
+    type Item = F::Item;
+    type Error = F::Error;
+
+    fn poll(&mut self) -> Poll<F::Item, F::Error> {
+        if self.token.is_cancelled() {
+            return Err(self.cancelled.take().expect("polled after cancelled"));
+        }
+        *self.token.0.waiting.borrow_mut() = Some(task::park());
+        self.future.poll()
+    }
+}
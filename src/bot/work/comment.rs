@@ -0,0 +1,76 @@
+use futures::{Future, future};
+use serde_json::{self, Value};
+
+use bot::client::Category;
+
+use super::task::{Context, Task, TaskError};
+
+/// The kind of `GithubComment` tasks.
+pub const GITHUB_COMMENT: &'static str = "github_comment";
+
+/// Posts a comment on an issue or pull request.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GithubComment {
+    pub host: String,
+    pub repo: String,
+    pub issue: u64,
+    pub body: String,
+}
+
+impl GithubComment {
+    pub fn new(host: String, repo: String, issue: u64, body: String) -> GithubComment {
+        GithubComment {
+            host: host,
+            repo: repo,
+            issue: issue,
+            body: body,
+        }
+    }
+}
+
+impl Task for GithubComment {
+    fn kind() -> &'static str {
+        GITHUB_COMMENT
+    }
+
+    fn describe(&self) -> String {
+        format!("comment on {}#{}", self.repo, self.issue)
+    }
+
+    // the client already retries what's worth retrying, so the task doesn't
+    fn execute(&self, cx: &Context) -> Box<Future<Item=(), Error=TaskError>> {
+        let client = match cx.github(&self.host) {
+            Some(client) => client,
+            None => {
+                error!("task failed: no github host named {:?}", self.host);
+                return Box::new(future::err(TaskError::fatal(format!("no github host named {:?}", self.host))));
+            }
+        };
+        let limits = client.clone();
+        Box::new(client.github_comment(self.repo.clone(), self.issue, self.body.clone())
+            .map_err(move |e| {
+                let category = e.category();
+                match category {
+                    Category::Auth => {
+                        error!("task failed, check the github token: {}", e);
+                    },
+                    Category::RateLimited => {
+                        error!("task failed ({}): {}, rate limit: {:?}",
+                               category, e, limits.rate_limit());
+                    },
+                    category => {
+                        error!("task failed ({}): {}", category, e);
+                    }
+                }
+                let reason = format!("{} ({})", e, category);
+                match category {
+                    Category::Retryable | Category::RateLimited => TaskError::retryable(reason),
+                    _ => TaskError::fatal(reason),
+                }
+            }))
+    }
+
+    fn to_json(&self) -> Value {
+        serde_json::to_value(self).expect("comments are plain data")
+    }
+}
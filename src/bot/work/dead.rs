@@ -0,0 +1,146 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+use serde_json;
+
+use super::Job;
+
+/// One time a task in a job failed.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Failure {
+    /// Seconds since the epoch.
+    pub at: u64,
+    /// Which of the job's tasks failed.
+    pub task: usize,
+    pub description: String,
+    pub error: String,
+}
+
+/// A job that failed, kept so it can be looked at and run again.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DeadLetter {
+    pub id: u64,
+    /// Seconds since the epoch.
+    pub died_at: u64,
+    /// Every failure, across every time the job was replayed.
+    pub errors: Vec<Failure>,
+    /// Set to start again at the task that failed, skipping any after it
+    /// that succeeded.
+    pub job: Job,
+}
+
+#[derive(Serialize, Deserialize)]
+enum Entry {
+    Added(DeadLetter),
+    Removed {
+        id: u64,
+    },
+}
+
+/// Dead letters, optionally backed by an append-only log of JSON lines like
+/// the queue's `LogStore`.
+pub struct DeadLetters {
+    letters: BTreeMap<u64, DeadLetter>,
+    file: Option<File>,
+}
+
+impl DeadLetters {
+    /// Kept in memory only; they're lost when the process exits.
+    pub fn memory() -> DeadLetters {
+        DeadLetters {
+            letters: BTreeMap::new(),
+            file: None,
+        }
+    }
+
+    /// Loads the log at `path`, compacting it down to the letters still in it.
+    pub fn open<P: Into<PathBuf>>(path: P) -> io::Result<DeadLetters> {
+        let path = path.into();
+        let letters = replay(&path)?;
+
+        let tmp = path.with_extension("compact");
+        {
+            let mut file = File::create(&tmp)?;
+            for letter in letters.values() {
+                write_entry(&mut file, &Entry::Added(letter.clone()))?;
+            }
+            file.sync_all()?;
+        }
+        fs::rename(&tmp, &path)?;
+
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)?;
+        Ok(DeadLetters {
+            letters: letters,
+            file: Some(file),
+        })
+    }
+
+    pub fn add(&mut self, letter: DeadLetter) -> io::Result<()> {
+        if let Some(ref mut file) = self.file {
+            write_entry(file, &Entry::Added(letter.clone()))?;
+        }
+        self.letters.insert(letter.id, letter);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, id: u64) -> io::Result<Option<DeadLetter>> {
+        if !self.letters.contains_key(&id) {
+            return Ok(None);
+        }
+        if let Some(ref mut file) = self.file {
+            write_entry(file, &Entry::Removed {
+                id: id,
+            })?;
+        }
+        Ok(self.letters.remove(&id))
+    }
+
+    pub fn get(&self, id: u64) -> Option<&DeadLetter> {
+        self.letters.get(&id)
+    }
+
+    /// Oldest first.
+    pub fn list(&self) -> Vec<&DeadLetter> {
+        self.letters.values().collect()
+    }
+}
+
+fn replay(path: &Path) -> io::Result<BTreeMap<u64, DeadLetter>> {
+    let mut letters = BTreeMap::new();
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(letters),
+        Err(e) => return Err(e),
+    };
+    for (i, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str(&line) {
+            Ok(Entry::Added(letter)) => {
+                letters.insert(letter.id, letter);
+            },
+            Ok(Entry::Removed { id }) => {
+                letters.remove(&id);
+            },
+            Err(e) => {
+                warn!("skipping bad line {} of {:?}: {}", i + 1, path, e);
+            }
+        }
+    }
+    Ok(letters)
+}
+
+fn write_entry(file: &mut File, entry: &Entry) -> io::Result<()> {
+    let line = serde_json::to_string(entry)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    file.write_all(line.as_bytes())?;
+    file.write_all(b"\n")?;
+    file.sync_data()
+}
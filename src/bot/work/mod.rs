@@ -0,0 +1,848 @@
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::io;
+use std::mem;
+use std::rc::Rc;
+use std::time::Duration;
+
+use futures::{Future, Stream, future, stream};
+use futures::sync::{mpsc, oneshot};
+
+use tokio_core::reactor::{Handle, Timeout};
+
+use serde::Deserialize;
+
+use bot::client::Client;
+use config::Config;
+use cron;
+
+pub use self::comment::{GITHUB_COMMENT, GithubComment};
+pub use self::dead::{DeadLetter, DeadLetters, Failure};
+pub use self::progress::ProgressComment;
+pub use self::react::{Acknowledge, REACT, React};
+pub use self::shell::{SHELL, Shell};
+pub use self::status::{JobState, JobStatus, TaskState, TaskStatus};
+pub use self::store::{LogStore, MemoryStore, Recovered, Store};
+pub use self::task::{Context, RetryPolicy, Task, TaskError};
+
+use self::cancel::CancelToken;
+use self::progress::Reporter;
+use self::scheduler::Scheduler;
+use self::status::History;
+use self::task::{Registry, TaskData};
+
+mod cancel;
+mod comment;
+mod dead;
+mod progress;
+mod react;
+mod recurring;
+mod scheduler;
+mod shell;
+mod status;
+mod store;
+mod task;
+
+#[derive(Clone)]
+pub struct Queue {
+    tx: mpsc::UnboundedSender<Job>,
+    store: Rc<RefCell<Box<Store>>>,
+    next_id: Rc<Cell<u64>>,
+    // jobs waiting or running; delayed ones don't count until they're due
+    depth: Rc<Cell<usize>>,
+    capacity: usize,
+    max_delayed: usize,
+    jobs: Rc<Jobs>,
+}
+
+/// Why a job couldn't be scheduled. The job is handed back either way.
+#[derive(Debug)]
+pub enum ScheduleError {
+    /// The queue is at capacity; try again later.
+    Full(Job),
+    /// The queue couldn't take the job at all.
+    Unavailable(Job),
+}
+
+impl Queue {
+    /// A queue backed by the store named in the config, or by memory if
+    /// there isn't one. Unfinished jobs from the store are started right away,
+    /// or when they're due if they were delayed, and the config's schedules
+    /// are set going.
+    pub fn new(config: &Config, client: Client, handle: &Handle) -> io::Result<Queue> {
+        let store: Box<Store> = match config.queue_path() {
+            Some(path) => {
+                info!("persisting jobs to {:?}", path);
+                Box::new(LogStore::open(path)?)
+            },
+            None => Box::new(MemoryStore),
+        };
+        let limits = Limits {
+            capacity: config.queue_capacity(),
+            max_delayed: config.queue_max_delayed(),
+            max_running: config.queue_max_running(),
+            history: config.queue_history(),
+        };
+        let dead = match config.dead_letter_path() {
+            Some(path) => DeadLetters::open(path)?,
+            None => DeadLetters::memory(),
+        };
+        let queue = Queue::with_store(store, dead, limits, client, handle)?;
+        recurring::start(config, &queue);
+        Ok(queue)
+    }
+
+    pub fn with_store(mut store: Box<Store>, dead: DeadLetters, limits: Limits, client: Client, handle: &Handle) -> io::Result<Queue> {
+        let recovered = store.recover()?;
+        let store = Rc::new(RefCell::new(store));
+        let depth = Rc::new(Cell::new(0));
+
+        let mut registry = Registry::new();
+        registry.register::<GithubComment>();
+        registry.register::<Shell>();
+        registry.register::<React>();
+
+        let (tx, rx) = mpsc::unbounded();
+        let jobs = Rc::new(Jobs {
+            client: client,
+            handle: handle.clone(),
+            store: store.clone(),
+            depth: depth.clone(),
+            scheduler: RefCell::new(Scheduler::new(limits.max_running)),
+            history: Rc::new(RefCell::new(History::new(limits.history))),
+            tokens: RefCell::new(HashMap::new()),
+            delayed: RefCell::new(HashSet::new()),
+            draining: Cell::new(false),
+            idle: RefCell::new(Vec::new()),
+            dead: RefCell::new(dead),
+            registry: RefCell::new(registry),
+            acks: RefCell::new(HashMap::new()),
+            seen: RefCell::new(HashMap::new()),
+        });
+        let recv = jobs.clone();
+        handle.spawn(rx.for_each(move |job| {
+            Jobs::on_recv(&recv, job);
+            Ok(())
+        }));
+        let queue = Queue {
+            tx: tx,
+            store: store,
+            next_id: Rc::new(Cell::new(recovered.next_id)),
+            depth: depth,
+            capacity: limits.capacity,
+            max_delayed: limits.max_delayed,
+            jobs: jobs,
+        };
+
+        if !recovered.jobs.is_empty() {
+            info!("resuming {} unfinished jobs", recovered.jobs.len());
+        }
+        // resumed jobs were already accepted once, so they may go over capacity
+        for job in recovered.jobs {
+            if let Err(e) = queue.send(job) {
+                error!("couldn't resume job: {:?}", e);
+            }
+        }
+        Ok(queue)
+    }
+
+    /// Lets jobs have `T` tasks, which are read back in by their kind.
+    /// Jobs resumed from the store don't start until the core runs, so
+    /// kinds registered before then are in time for them.
+    pub fn register<T: Task + Deserialize + 'static>(&self) {
+        self.jobs.registry.borrow_mut().register::<T>();
+    }
+
+    /// How many jobs are waiting or running.
+    pub fn depth(&self) -> usize {
+        self.depth.get()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// How many jobs are waiting for their time to come, such as reminders.
+    pub fn delayed(&self) -> usize {
+        self.jobs.delayed.borrow().len()
+    }
+
+    pub fn max_delayed(&self) -> usize {
+        self.max_delayed
+    }
+
+    /// How many jobs are running right now.
+    pub fn running(&self) -> usize {
+        self.jobs.scheduler.borrow().running()
+    }
+
+    /// How many jobs are waiting for their turn to run.
+    pub fn waiting(&self) -> usize {
+        self.jobs.scheduler.borrow().waiting()
+    }
+
+    /// What's known about job `id`, if it's unfinished or finished
+    /// recently enough to still be remembered.
+    pub fn status(&self, id: u64) -> Option<JobStatus> {
+        self.jobs.history.borrow().get(id).cloned()
+    }
+
+    /// Every job still remembered, newest first.
+    pub fn statuses(&self) -> Vec<JobStatus> {
+        self.jobs.history.borrow().list().into_iter().cloned().collect()
+    }
+
+    /// The newest unfinished job that `requester` asked for on `host`, not
+    /// counting delayed ones; those are forgotten rather than cancelled.
+    pub fn latest_job(&self, host: &str, requester: &str) -> Option<u64> {
+        self.jobs.history.borrow().latest_unfinished(host, requester)
+    }
+
+    /// Delayed jobs that `requester` asked for on `host` and that haven't
+    /// started yet, soonest first.
+    pub fn delayed_jobs(&self, host: &str, requester: &str) -> Vec<JobStatus> {
+        let mut jobs: Vec<JobStatus> = self.jobs.history.borrow().list().into_iter()
+            .filter(|status| status.state == JobState::Queued && status.run_at.is_some())
+            .filter(|status| {
+                status.origin.as_ref()
+                    .map_or(false, |origin| origin.host == host && origin.requester == requester)
+            })
+            .cloned()
+            .collect();
+        jobs.sort_by_key(|status| status.run_at);
+        jobs
+    }
+
+    /// Cancels job `id`. A waiting job never starts; a running one has its
+    /// current task stopped and the rest skipped. Returns false if there's no
+    /// such unfinished job.
+    pub fn cancel(&self, id: u64) -> bool {
+        let token = match self.jobs.tokens.borrow().get(&id) {
+            Some(token) => token.clone(),
+            None => return false,
+        };
+        info!("cancelling job {}", id);
+        token.cancel();
+        let waiting = self.jobs.scheduler.borrow_mut().remove(id).is_some();
+        let delayed = self.jobs.delayed.borrow_mut().remove(&id);
+        if waiting || delayed {
+            // it never started, so nothing else will finish it
+            self.jobs.finished(id, JobState::Cancelled, waiting);
+        }
+        true
+    }
+
+    /// Failed jobs, oldest first.
+    pub fn dead_letters(&self) -> Vec<DeadLetter> {
+        self.jobs.dead.borrow().list().into_iter().cloned().collect()
+    }
+
+    pub fn dead_letter(&self, id: u64) -> Option<DeadLetter> {
+        self.jobs.dead.borrow().get(id).cloned()
+    }
+
+    /// Schedules dead letter `id` again as a new job, from the task that
+    /// failed, skipping any after it that succeeded, and gives back the new
+    /// job's id. `Ok(None)` means there's no
+    /// such dead letter.
+    pub fn replay(&self, id: u64) -> Result<Option<u64>, ScheduleError> {
+        let letter = match self.dead_letter(id) {
+            Some(letter) => letter,
+            None => return Ok(None),
+        };
+        let mut job = letter.job;
+        job.id = self.new_job().id;
+        job.errors = letter.errors;
+        let new_id = job.id;
+        self.schedule(job)?;
+        info!("replaying dead letter {} as job {}", id, new_id);
+        if let Err(e) = self.jobs.dead.borrow_mut().remove(id) {
+            error!("failed to persist removal of dead letter {}: {}", id, e);
+        }
+        Ok(Some(new_id))
+    }
+
+    /// Throws dead letter `id` away. False if there's no such dead letter.
+    pub fn discard(&self, id: u64) -> io::Result<bool> {
+        self.jobs.dead.borrow_mut().remove(id).map(|letter| letter.is_some())
+    }
+
+    /// Stops starting jobs, and resolves once the running ones are done.
+    /// Everything unfinished stays in the store, to resume after a restart.
+    pub fn drain(&self) -> Box<Future<Item=(), Error=()>> {
+        self.jobs.draining.set(true);
+        if self.running() == 0 {
+            return Box::new(future::ok(()));
+        }
+        let (tx, rx) = oneshot::channel();
+        self.jobs.idle.borrow_mut().push(tx);
+        Box::new(rx.map_err(|_| ()))
+    }
+
+    /// A new, empty job with an id of its own, so the id can be mentioned
+    /// before the job is scheduled.
+    pub fn new_job(&self) -> Job {
+        let id = self.next_id.get();
+        self.next_id.set(id + 1);
+        Job::new(id)
+    }
+
+    pub fn schedule(&self, job: Job) -> Result<(), ScheduleError> {
+        self.schedule_all(vec![job])
+    }
+
+    /// Schedules every one of `jobs`, or none of them. If one doesn't fit
+    /// or can't be persisted, it's the one handed back.
+    pub fn schedule_all(&self, mut jobs: Vec<Job>) -> Result<(), ScheduleError> {
+        let now = cron::now();
+        let (mut depth, mut delayed) = (self.depth.get(), self.delayed());
+        // jobs for later don't take up room for jobs for now
+        let full = jobs.iter().position(|job| {
+            if job.run_at.map_or(false, |at| at > now) {
+                delayed += 1;
+                delayed > self.max_delayed
+            } else {
+                depth += 1;
+                depth > self.capacity
+            }
+        });
+        if let Some(i) = full {
+            let job = jobs.remove(i);
+            warn!("queue is full ({} jobs, {} delayed), rejecting job {}", self.depth.get(), self.delayed(), job.id);
+            return Err(ScheduleError::Full(job));
+        }
+
+        // the timers for jobs for later are set before anything is saved or
+        // sent, so there's nothing to undo if one can't be
+        let timers = jobs.iter().map(|job| self.timer(job, now)).collect::<Vec<_>>();
+        if let Some(i) = timers.iter().position(Result::is_err) {
+            return Err(ScheduleError::Unavailable(jobs.remove(i)));
+        }
+        let timers = timers.into_iter().filter_map(Result::ok);
+
+        let unsaved = jobs.iter().position(|job| {
+            match self.store.borrow_mut().scheduled(job) {
+                Ok(()) => false,
+                Err(e) => {
+                    error!("failed to persist job {}: {}", job.id, e);
+                    true
+                }
+            }
+        });
+        if let Some(i) = unsaved {
+            // forget the ones before it again
+            for job in &jobs[..i] {
+                if let Err(e) = self.store.borrow_mut().finished(job.id) {
+                    error!("failed to persist removal of job {}: {}", job.id, e);
+                }
+            }
+            return Err(ScheduleError::Unavailable(jobs.remove(i)));
+        }
+
+        let ids = jobs.iter().map(|job| job.id).collect::<Vec<_>>();
+        let mut sent = Vec::new();
+        for (job, timer) in jobs.into_iter().zip(timers) {
+            let id = job.id;
+            let ack = job.acknowledge.clone();
+            if let Err(e) = self.send(job, timer) {
+                // take back the ones already on their way, and forget the
+                // rest again
+                for &(id, _) in &sent {
+                    self.jobs.acks.borrow_mut().remove(&id);
+                    self.cancel(id);
+                }
+                for &id in &ids[sent.len()..] {
+                    if let Err(e) = self.store.borrow_mut().finished(id) {
+                        error!("failed to persist removal of job {}: {}", id, e);
+                    }
+                }
+                return Err(e);
+            }
+            sent.push((id, ack));
+        }
+        for (id, ack) in sent {
+            if let Some(ref ack) = ack {
+                if let Some(seen) = react::accepted(&self.jobs.client, &self.jobs.handle, ack) {
+                    self.jobs.seen.borrow_mut().insert(id, seen);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // the timer a job for later waits on, if it is one
+    fn timer(&self, job: &Job, now: u64) -> Result<Option<(Timeout, Duration)>, ()> {
+        let delay = match job.run_at {
+            Some(at) if at > now => Duration::from_secs(at - now),
+            _ => return Ok(None),
+        };
+        Timeout::new(delay, &self.jobs.handle)
+            .map(|timeout| Some((timeout, delay)))
+            .map_err(|e| error!("couldn't set a timer for job {}: {}", job.id, e))
+    }
+
+    fn send(&self, job: Job, timer: Option<(Timeout, Duration)>) -> Result<(), ScheduleError> {
+        self.jobs.history.borrow_mut().queued(&job);
+        let id = job.id;
+        let ack = job.acknowledge.clone();
+        let sent = match timer {
+            Some((timeout, delay)) => {
+                self.delay(job, timeout, delay);
+                Ok(())
+            },
+            None => self.tx.send(job).map_err(|e| e.into_inner()),
+        };
+        let delayed = self.jobs.delayed.borrow().contains(&id);
+        sent.map(|()| {
+            if !delayed {
+                self.depth.set(self.depth.get() + 1);
+            }
+            self.jobs.tokens.borrow_mut().insert(id, CancelToken::new());
+            if let Some(ack) = ack {
+                self.jobs.acks.borrow_mut().insert(id, ack);
+            }
+        }).map_err(|job| {
+            self.jobs.history.borrow_mut().finished(id, JobState::Failed);
+            ScheduleError::Unavailable(job)
+        })
+    }
+
+    // hands the job to the runner once `delay` is up, unless it's been
+    // cancelled by then
+    fn delay(&self, job: Job, timeout: Timeout, delay: Duration) {
+        debug!("job {} will start in {}s", job.id, delay.as_secs());
+        let id = job.id;
+        self.jobs.delayed.borrow_mut().insert(id);
+        let tx = self.tx.clone();
+        let jobs = self.jobs.clone();
+        self.jobs.handle.spawn(timeout.then(move |_| {
+            if !jobs.delayed.borrow_mut().remove(&id) {
+                // cancelled while it waited
+                return Ok(());
+            }
+            // due now, so it counts like any other
+            jobs.depth.set(jobs.depth.get() + 1);
+            if tx.send(job).is_err() {
+                error!("couldn't start delayed job {}", id);
+                jobs.finished(id, JobState::Failed, true);
+            }
+            Ok(())
+        }));
+    }
+}
+
+/// How much work a `Queue` takes on.
+#[derive(Clone, Copy, Debug)]
+pub struct Limits {
+    /// How many jobs may be waiting or running before new ones are refused.
+    pub capacity: usize,
+    /// How many jobs may be waiting for a later time, separately.
+    pub max_delayed: usize,
+    /// How many jobs may run at once.
+    pub max_running: usize,
+    /// How many finished jobs to remember.
+    pub history: usize,
+}
+
+struct Jobs {
+    client: Client,
+    handle: Handle,
+    store: Rc<RefCell<Box<Store>>>,
+    depth: Rc<Cell<usize>>,
+    scheduler: RefCell<Scheduler>,
+    history: Rc<RefCell<History>>,
+    // one for each job that's waiting or running
+    tokens: RefCell<HashMap<u64, CancelToken>>,
+    // jobs whose time hasn't come yet
+    delayed: RefCell<HashSet<u64>>,
+    // shutting down; start nothing new
+    draining: Cell<bool>,
+    // told when the last running job finishes while draining
+    idle: RefCell<Vec<oneshot::Sender<()>>>,
+    dead: RefCell<DeadLetters>,
+    // how to turn each kind of task back into something to run
+    registry: RefCell<Registry>,
+    // comments to react to once their jobs finish
+    acks: RefCell<HashMap<u64, Acknowledge>>,
+    // the :eyes: on those comments, once it's there
+    seen: RefCell<HashMap<u64, react::Seen>>,
+}
+
+impl Jobs {
+    fn on_recv(jobs: &Rc<Jobs>, job: Job) {
+        trace!("queuing new job: {:?}", job);
+        jobs.scheduler.borrow_mut().push(job);
+        Jobs::start_next(jobs);
+    }
+
+    // start as many waiting jobs as the scheduler allows
+    fn start_next(jobs: &Rc<Jobs>) {
+        if jobs.draining.get() {
+            if jobs.scheduler.borrow().running() == 0 {
+                for tx in jobs.idle.borrow_mut().drain(..) {
+                    let _ = tx.send(());
+                }
+            }
+            return;
+        }
+        loop {
+            let job = jobs.scheduler.borrow_mut().next();
+            match job {
+                Some(job) => Jobs::run(jobs, job),
+                None => return,
+            }
+        }
+    }
+
+    // the bookkeeping for a job that won't run any more tasks; `counted` if
+    // it was in the depth, rather than still delayed
+    fn finished(&self, id: u64, state: JobState, counted: bool) {
+        debug!("job {} {:?}", id, state);
+        if counted {
+            self.depth.set(self.depth.get() - 1);
+        }
+        if let Err(e) = self.store.borrow_mut().finished(id) {
+            error!("failed to persist completion of job {}: {}", id, e);
+        }
+        self.history.borrow_mut().finished(id, state);
+        self.tokens.borrow_mut().remove(&id);
+        let ack = self.acks.borrow_mut().remove(&id);
+        let seen = self.seen.borrow_mut().remove(&id);
+        if let Some(ack) = ack {
+            react::finished(&self.client, &self.handle, ack, seen, state);
+        }
+    }
+
+    // keeps a failed job, starting from its first failed task, and
+    // remembering which tasks after that already succeeded
+    fn bury(&self, mut job: Job, failures: Vec<Failure>, succeeded: BTreeSet<usize>) {
+        let id = job.id;
+        if job.disposable {
+            debug!("job {} failed, but isn't worth keeping", id);
+            return;
+        }
+        if let Some(first) = failures.iter().map(|f| f.task).min() {
+            job.done = first;
+        }
+        job.succeeded.extend(succeeded);
+        let mut errors = mem::replace(&mut job.errors, Vec::new());
+        errors.extend(failures);
+        warn!("job {} failed, keeping it as a dead letter", id);
+        let letter = DeadLetter {
+            id: id,
+            died_at: cron::now(),
+            errors: errors,
+            job: job,
+        };
+        if let Err(e) = self.dead.borrow_mut().add(letter) {
+            error!("failed to persist dead letter {}: {}", id, e);
+        }
+    }
+
+    fn run(jobs: &Rc<Jobs>, job: Job) {
+        debug!("starting job {}", job.id);
+        let original = job.clone();
+        let id = job.id;
+        let token = jobs.tokens.borrow().get(&id).cloned().unwrap_or_else(CancelToken::new);
+        let keys = job.keys();
+        let origin = job.origin.clone();
+        let keep_going = job.keep_going;
+        jobs.history.borrow_mut().started(id);
+        let reporter = job.progress.clone().and_then(|target| {
+            Reporter::start(&jobs.client, &jobs.handle, jobs.history.clone(), jobs.store.clone(), id, target)
+        });
+        let reports = reporter.clone();
+        let context = Context::new(jobs.client.clone(), jobs.handle.clone(), id, jobs.history.clone());
+        let kinds = jobs.clone();
+        let progress = jobs.store.clone();
+        let history = jobs.history.clone();
+        let cancel = token.clone();
+        let failures = Rc::new(RefCell::new(Vec::new()));
+        let failed_tasks = failures.clone();
+        let succeeded = Rc::new(RefCell::new(BTreeSet::new()));
+        let succeeded_tasks = succeeded.clone();
+        let already = job.succeeded;
+        let tasks = job.tasks.into_iter().enumerate().skip(job.done)
+            .filter(move |&(i, _)| !already.contains(&i));
+        let steps = stream::iter(tasks.map(|t| Ok::<_, ()>(t))).fold(Vec::new(), move |mut steps: Vec<TaskStatus>, (i, task)| -> Box<Future<Item=Vec<TaskStatus>, Error=()>> {
+            let progress = progress.clone();
+            let history = history.clone();
+            let failures = failed_tasks.clone();
+            let succeeded = succeeded_tasks.clone();
+            let reporter = reports.clone();
+            let description = task.description.clone();
+            let failed = steps.iter().any(|step| step.state == TaskState::Failed);
+            let run: Box<Future<Item=(), Error=Stop>> = if cancel.is_cancelled() || (failed && !keep_going) {
+                Box::new(future::err(Stop::Skipped))
+            } else {
+                history.borrow_mut().task_started(id, i);
+                if let Some(ref reporter) = reporter {
+                    Reporter::update(reporter);
+                }
+                let decoded = kinds.registry.borrow().decode(&task);
+                match decoded {
+                    Ok(runnable) => {
+                        let run = task::execute(Rc::new(runnable), context.clone())
+                            .map_err(|e| Stop::Failed(e.message().to_string()));
+                        Box::new(cancel.guard(run, Stop::Cancelled))
+                    },
+                    Err(reason) => {
+                        error!("job {} can't run {:?}: {}", id, description, reason);
+                        Box::new(future::err(Stop::Failed(reason)))
+                    }
+                }
+            };
+            Box::new(run.then(move |result| {
+                let (state, error) = match result {
+                    Ok(()) => (TaskState::Succeeded, None),
+                    Err(Stop::Failed(reason)) => (TaskState::Failed, Some(reason)),
+                    Err(Stop::Skipped) => (TaskState::Skipped, None),
+                    Err(Stop::Cancelled) => (TaskState::Cancelled, None),
+                };
+                // skipped tasks count as done, so a restart doesn't run them
+                if let Err(e) = progress.borrow_mut().progress(id, i + 1) {
+                    error!("failed to persist progress of job {}: {}", id, e);
+                }
+                history.borrow_mut().task(id, i, state, error.clone());
+                if state == TaskState::Succeeded {
+                    succeeded.borrow_mut().insert(i);
+                }
+                if let Some(ref reporter) = reporter {
+                    Reporter::update(reporter);
+                }
+                if let Some(ref error) = error {
+                    failures.borrow_mut().push(Failure {
+                        at: cron::now(),
+                        task: i,
+                        description: description.clone(),
+                        error: error.clone(),
+                    });
+                }
+                steps.push(TaskStatus {
+                    task: description,
+                    state: state,
+                    error: error,
+                    started_at: None,
+                    finished_at: None,
+                });
+                Ok::<_, ()>(steps)
+            }))
+        });
+
+        let client = jobs.client.clone();
+        let failed = Rc::new(Cell::new(false));
+        let report_failed = failed.clone();
+        let cancelled = token.clone();
+        let has_progress = reporter.is_some();
+        let report = steps.and_then(move |steps| -> Box<Future<Item=(), Error=()>> {
+            // whoever cancelled it already knows
+            if cancelled.is_cancelled() || !steps.iter().any(|step| step.state == TaskState::Failed) {
+                return Box::new(future::ok(()));
+            }
+            report_failed.set(true);
+            // the progress comment already says so
+            if has_progress {
+                return Box::new(future::ok(()));
+            }
+            let origin = match origin {
+                Some(origin) => origin,
+                None => return Box::new(future::ok(())),
+            };
+            let client = match client.host(&origin.host) {
+                Some(client) => client,
+                None => {
+                    error!("can't report failure of job {}: no github host named {:?}", id, origin.host);
+                    return Box::new(future::ok(()));
+                }
+            };
+            let body = failure_summary(id, &origin, &steps);
+            Box::new(client.github_comment(origin.repo, origin.issue, body)
+                .map_err(move |e| {
+                    error!("couldn't report failure of job {}: {}", id, e);
+                }))
+        });
+
+        let this = jobs.clone();
+        jobs.handle.spawn(report.then(move |result| {
+            let state = if token.is_cancelled() {
+                JobState::Cancelled
+            } else if failed.get() {
+                JobState::Failed
+            } else {
+                JobState::Succeeded
+            };
+            if state == JobState::Failed {
+                let failures = failures.borrow_mut().drain(..).collect();
+                let succeeded = mem::replace(&mut *succeeded.borrow_mut(), BTreeSet::new());
+                this.bury(original, failures, succeeded);
+            }
+            this.finished(id, state, true);
+            if let Some(ref reporter) = reporter {
+                Reporter::finish(reporter);
+            }
+            this.scheduler.borrow_mut().done(&keys);
+            Jobs::start_next(&this);
+            result
+        }));
+    }
+}
+
+// Why a task didn't succeed.
+enum Stop {
+    Failed(String),
+    Skipped,
+    Cancelled,
+}
+
+fn failure_summary(id: u64, origin: &Origin, steps: &[TaskStatus]) -> String {
+    let mut body = format!("@{} I couldn't finish job {}. :warning:\n", origin.requester, id);
+    for step in steps {
+        let line = match (step.state, step.error.as_ref()) {
+            (TaskState::Failed, Some(reason)) => format!("\n- :x: {}: {}", step.task, reason),
+            (TaskState::Failed, None) => format!("\n- :x: {}", step.task),
+            (TaskState::Skipped, _) => format!("\n- :fast_forward: {} (skipped)", step.task),
+            (TaskState::Cancelled, _) => format!("\n- :stop_sign: {} (cancelled)", step.task),
+            _ => format!("\n- :white_check_mark: {}", step.task),
+        };
+        body.push_str(&line);
+    }
+    body
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Job {
+    id: u64,
+    // jobs that share a key never run at the same time
+    #[serde(default)]
+    keys: Vec<String>,
+    #[serde(default)]
+    origin: Option<Origin>,
+    // run the rest of the tasks even after one fails
+    #[serde(default)]
+    keep_going: bool,
+    // seconds since the epoch; don't start before then
+    #[serde(default)]
+    run_at: Option<u64>,
+    #[serde(default)]
+    description: Option<String>,
+    // a comment kept up to date as the job runs
+    #[serde(default)]
+    progress: Option<ProgressComment>,
+    // a comment reacted to as the job goes
+    #[serde(default)]
+    acknowledge: Option<Acknowledge>,
+    // failures from earlier runs, when replaying a dead letter
+    #[serde(default)]
+    errors: Vec<Failure>,
+    // tasks past `done` that already succeeded, when replaying a dead letter
+    #[serde(default)]
+    succeeded: BTreeSet<usize>,
+    // not kept as a dead letter if it fails
+    #[serde(default)]
+    disposable: bool,
+    tasks: Vec<TaskData>,
+    // how many of `tasks` have already completed
+    done: usize,
+}
+
+impl Job {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    fn new(id: u64) -> Job {
+        Job {
+            id: id,
+            keys: Vec::new(),
+            origin: None,
+            keep_going: false,
+            run_at: None,
+            description: None,
+            progress: None,
+            acknowledge: None,
+            errors: Vec::new(),
+            succeeded: BTreeSet::new(),
+            disposable: false,
+            tasks: Vec::new(),
+            done: 0,
+        }
+    }
+
+    /// Don't run this job alongside any other job with the same key, such
+    /// as the repo it works on or the environment it deploys to. A job can
+    /// have several keys; the first decides whose turn it waits for.
+    pub fn serialize_on(&mut self, key: String) {
+        if !self.keys.contains(&key) {
+            self.keys.push(key);
+        }
+    }
+
+    fn keys(&self) -> Vec<String> {
+        self.keys.clone()
+    }
+
+    /// Report failures back to where the job was asked for.
+    pub fn set_origin(&mut self, origin: Origin) {
+        self.origin = Some(origin);
+    }
+
+    /// Whether to run the remaining tasks after one fails, rather than
+    /// skipping them.
+    pub fn keep_going(&mut self, keep_going: bool) {
+        self.keep_going = keep_going;
+    }
+
+    /// Don't start before `at`, in seconds since the epoch. Delayed jobs are
+    /// persisted like any other, so they still run after a restart.
+    pub fn run_at(&mut self, at: u64) {
+        self.run_at = Some(at);
+    }
+
+    /// What the job is for, in a few words, for listings.
+    pub fn set_description(&mut self, description: String) {
+        self.description = Some(description);
+    }
+
+    /// Keep one comment up to date with how the job is doing, instead of
+    /// commenting again when it fails.
+    pub fn report_progress(&mut self, comment: ProgressComment) {
+        self.progress = Some(comment);
+    }
+
+    /// React to a comment when the job is scheduled, and again when it's
+    /// done.
+    pub fn acknowledge(&mut self, ack: Acknowledge) {
+        self.acknowledge = Some(ack);
+    }
+
+    /// Don't keep the job as a dead letter if it fails, as when all it does
+    /// is reply to a comment.
+    pub fn disposable(&mut self) {
+        self.disposable = true;
+    }
+
+    /// Whether the job has no tasks yet.
+    pub fn is_empty(&self) -> bool {
+        self.tasks.is_empty()
+    }
+
+    /// Adds a task to run after the ones already in the job. Its kind has
+    /// to be registered with the queue by the time the job runs.
+    pub fn push<T: Task>(&mut self, task: &T) {
+        self.tasks.push(TaskData::new(task));
+    }
+
+    pub fn comment(&mut self, host: String, repo: String, issue: u64, body: String) {
+        self.push(&GithubComment::new(host, repo, issue, body));
+    }
+}
+
+/// The comment that asked for a job.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Origin {
+    pub host: String,
+    pub repo: String,
+    pub issue: u64,
+    pub comment: u64,
+    pub requester: String,
+}
@@ -0,0 +1,313 @@
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::time::Duration;
+
+use futures::Future;
+use tokio_core::reactor::{Handle, Timeout};
+
+use bot::client::Client;
+use cron;
+
+use super::status::{History, JobState, JobStatus, TaskState};
+use super::store::Store;
+
+// how often a running job's comment is redrawn, for the time and the log
+const REFRESH_SECS: u64 = 15;
+// how much of the log to show
+const LOG_TAIL: usize = 20;
+
+/// Where a job keeps its progress comment.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ProgressComment {
+    pub host: String,
+    pub repo: String,
+    pub issue: u64,
+    /// Mentioned when the comment is first posted.
+    pub requester: Option<String>,
+    /// The comment, once it's been posted.
+    #[serde(default)]
+    pub comment: Option<u64>,
+}
+
+/// Keeps one comment up to date with how a running job is doing.
+///
+/// The comment is posted when the job starts and edited as its tasks
+/// progress, rather than posting a comment for every step. Edits go out one
+/// at a time; if several updates pile up while one is in flight, only the
+/// newest is sent. The comment's id is kept with the job, so a job resumed
+/// after a restart edits the same comment.
+pub struct Reporter {
+    client: Client,
+    handle: Handle,
+    history: Rc<RefCell<History>>,
+    store: Rc<RefCell<Box<Store>>>,
+    id: u64,
+    target: ProgressComment,
+    comment: Cell<Option<u64>>,
+    // the body GitHub has, or is about to have
+    shown: RefCell<String>,
+    pending: RefCell<Option<String>>,
+    sending: Cell<bool>,
+    done: Cell<bool>,
+}
+
+impl Reporter {
+    pub fn start(client: &Client, handle: &Handle, history: Rc<RefCell<History>>, store: Rc<RefCell<Box<Store>>>,
+                 id: u64, target: ProgressComment)
+        -> Option<Rc<Reporter>>
+    {
+        let client = match client.host(&target.host) {
+            Some(client) => client,
+            None => {
+                error!("can't report progress of job {}: no github host named {:?}", id, target.host);
+                return None;
+            }
+        };
+        let reporter = Rc::new(Reporter {
+            client: client,
+            handle: handle.clone(),
+            history: history,
+            store: store,
+            id: id,
+            comment: Cell::new(target.comment),
+            target: target,
+            shown: RefCell::new(String::new()),
+            pending: RefCell::new(None),
+            sending: Cell::new(false),
+            done: Cell::new(false),
+        });
+        Reporter::update(&reporter);
+        Reporter::tick(reporter.clone());
+        Some(reporter)
+    }
+
+    /// Redraws the comment, if anything changed.
+    pub fn update(this: &Rc<Reporter>) {
+        let body = match this.history.borrow().get(this.id) {
+            Some(status) => render(status, this.target.requester.as_ref().map(AsRef::as_ref), cron::now()),
+            None => return,
+        };
+        if *this.shown.borrow() == body {
+            return;
+        }
+        *this.shown.borrow_mut() = body.clone();
+        *this.pending.borrow_mut() = Some(body);
+        Reporter::send(this);
+    }
+
+    /// Draws the comment one last time, and stops redrawing it.
+    pub fn finish(this: &Rc<Reporter>) {
+        this.done.set(true);
+        Reporter::update(this);
+    }
+
+    fn send(this: &Rc<Reporter>) {
+        if this.sending.get() {
+            return;
+        }
+        let body = match this.pending.borrow_mut().take() {
+            Some(body) => body,
+            None => return,
+        };
+        this.sending.set(true);
+        let id = this.id;
+        let sent: Box<Future<Item=u64, Error=()>> = match this.comment.get() {
+            Some(comment) => Box::new(this.client.edit_comment(&this.target.repo, comment, &body)
+                .map(|comment| comment.id)
+                .map_err(move |e| error!("couldn't update progress of job {}: {}", id, e))),
+            None => Box::new(this.client.create_comment(&this.target.repo, this.target.issue, &body)
+                .map(|comment| comment.id)
+                .map_err(move |e| error!("couldn't report progress of job {}: {}", id, e))),
+        };
+        let reporter = this.clone();
+        this.handle.spawn(sent.then(move |result| {
+            if let Ok(comment) = result {
+                if reporter.comment.get().is_none() {
+                    if let Err(e) = reporter.store.borrow_mut().commented(id, comment) {
+                        error!("failed to persist the progress comment of job {}: {}", id, e);
+                    }
+                }
+                reporter.comment.set(Some(comment));
+            }
+            reporter.sending.set(false);
+            Reporter::send(&reporter);
+            Ok(())
+        }));
+    }
+
+    fn tick(this: Rc<Reporter>) {
+        let timeout = match Timeout::new(Duration::from_secs(REFRESH_SECS), &this.handle) {
+            Ok(timeout) => timeout,
+            Err(e) => {
+                error!("couldn't set a timer for the progress of job {}: {}", this.id, e);
+                return;
+            }
+        };
+        let handle = this.handle.clone();
+        handle.spawn(timeout.then(move |_| {
+            if !this.done.get() {
+                Reporter::update(&this);
+                Reporter::tick(this);
+            }
+            Ok(())
+        }));
+    }
+}
+
+/// The progress comment for a job, as of `now`.
+pub fn render(status: &JobStatus, requester: Option<&str>, now: u64) -> String {
+    let title = status.description.clone().unwrap_or_else(|| format!("job {}", status.id));
+    let started = status.started_at.unwrap_or(now);
+    let elapsed = format_duration(status.finished_at.unwrap_or(now).saturating_sub(started));
+    let headline = match status.state {
+        JobState::Queued => format!(":hourglass: {} is waiting to start", title),
+        JobState::Running => format!(":hourglass: {} is running ({} so far)", title, elapsed),
+        JobState::Succeeded => format!(":white_check_mark: {} finished in {}", title, elapsed),
+        JobState::Failed => format!(":x: {} failed after {}", title, elapsed),
+        JobState::Cancelled => format!(":stop_sign: {} was cancelled after {}", title, elapsed),
+    };
+    let mut body = match requester {
+        Some(requester) => format!("@{} {} (job {})\n", requester, headline, status.id),
+        None => format!("{} (job {})\n", headline, status.id),
+    };
+    for task in &status.tasks {
+        let took = match (task.started_at, task.finished_at) {
+            (Some(start), Some(end)) => format!(" ({})", format_duration(end.saturating_sub(start))),
+            (Some(start), None) => format!(" ({} so far)", format_duration(now.saturating_sub(start))),
+            _ => String::new(),
+        };
+        let line = match (task.state, task.error.as_ref()) {
+            (TaskState::Pending, _) => format!("\n- :white_large_square: {}", task.task),
+            (TaskState::Running, _) => format!("\n- :hourglass: {}{}", task.task, took),
+            (TaskState::Succeeded, _) => format!("\n- :white_check_mark: {}{}", task.task, took),
+            (TaskState::Failed, Some(reason)) => format!("\n- :x: {}{}: {}", task.task, took, reason),
+            (TaskState::Failed, None) => format!("\n- :x: {}{}", task.task, took),
+            (TaskState::Skipped, _) => format!("\n- :fast_forward: {} (skipped)", task.task),
+            (TaskState::Cancelled, _) => format!("\n- :stop_sign: {} (cancelled)", task.task),
+        };
+        body.push_str(&line);
+    }
+    if !status.log.is_empty() {
+        let skip = status.log.len().saturating_sub(LOG_TAIL);
+        let summary = if skip > 0 {
+            format!("last {} lines of the log", LOG_TAIL)
+        } else {
+            "log".to_string()
+        };
+        body.push_str(&format!("\n\n<details><summary>{}</summary>\n\n<pre>", summary));
+        for line in &status.log[skip..] {
+            body.push_str(&escape_html(line));
+            body.push('\n');
+        }
+        body.push_str("</pre>\n</details>");
+    }
+    body
+}
+
+/// Like `3m12s`.
+pub fn format_duration(secs: u64) -> String {
+    let (h, m, s) = (secs / 3600, secs / 60 % 60, secs % 60);
+    if h > 0 {
+        format!("{}h{:02}m{:02}s", h, m, s)
+    } else if m > 0 {
+        format!("{}m{:02}s", m, s)
+    } else {
+        format!("{}s", s)
+    }
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_duration, render};
+    use super::super::status::{JobState, JobStatus, TaskState, TaskStatus};
+
+    fn job(state: JobState, started_at: Option<u64>, finished_at: Option<u64>) -> JobStatus {
+        JobStatus {
+            id: 7,
+            state: state,
+            description: None,
+            origin: None,
+            queued_at: 90,
+            run_at: None,
+            started_at: started_at,
+            finished_at: finished_at,
+            tasks: Vec::new(),
+            log: Vec::new(),
+        }
+    }
+
+    fn task(name: &str, state: TaskState, started_at: Option<u64>, finished_at: Option<u64>) -> TaskStatus {
+        TaskStatus {
+            task: name.to_string(),
+            state: state,
+            error: None,
+            started_at: started_at,
+            finished_at: finished_at,
+        }
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(0), "0s");
+        assert_eq!(format_duration(59), "59s");
+        assert_eq!(format_duration(60), "1m00s");
+        assert_eq!(format_duration(3599), "59m59s");
+        assert_eq!(format_duration(3600), "1h00m00s");
+        assert_eq!(format_duration(3725), "1h02m05s");
+    }
+
+    #[test]
+    fn test_render_states() {
+        let status = job(JobState::Queued, None, None);
+        assert_eq!(render(&status, None, 100), ":hourglass: job 7 is waiting to start (job 7)\n");
+
+        let mut status = job(JobState::Running, Some(100), None);
+        status.description = Some("deploy of a/b".to_string());
+        status.tasks = vec![
+            task("build", TaskState::Succeeded, Some(100), Some(160)),
+            task("deploy", TaskState::Running, Some(160), None),
+            task("notify", TaskState::Pending, None, None),
+        ];
+        assert_eq!(render(&status, Some("alice"), 295),
+                   "@alice :hourglass: deploy of a/b is running (3m15s so far) (job 7)\n\
+                    \n- :white_check_mark: build (1m00s)\
+                    \n- :hourglass: deploy (2m15s so far)\
+                    \n- :white_large_square: notify");
+
+        // a finished job's time stops when it finished
+        let mut status = job(JobState::Failed, Some(100), Some(400));
+        let mut build = task("build", TaskState::Failed, Some(100), Some(160));
+        build.error = Some("exit 1".to_string());
+        status.tasks = vec![build, task("deploy", TaskState::Skipped, None, None)];
+        assert_eq!(render(&status, None, 1000),
+                   ":x: job 7 failed after 5m00s (job 7)\n\
+                    \n- :x: build (1m00s): exit 1\
+                    \n- :fast_forward: deploy (skipped)");
+
+        let status = job(JobState::Succeeded, Some(100), Some(105));
+        assert_eq!(render(&status, None, 1000), ":white_check_mark: job 7 finished in 5s (job 7)\n");
+        let status = job(JobState::Cancelled, Some(100), Some(220));
+        assert_eq!(render(&status, None, 1000), ":stop_sign: job 7 was cancelled after 2m00s (job 7)\n");
+    }
+
+    #[test]
+    fn test_render_log_tail() {
+        let mut status = job(JobState::Running, Some(100), None);
+        status.log = vec!["<b>bold</b> & more".to_string()];
+        assert_eq!(render(&status, None, 100),
+                   ":hourglass: job 7 is running (0s so far) (job 7)\n\
+                    \n\n<details><summary>log</summary>\n\n\
+                    <pre>&lt;b&gt;bold&lt;/b&gt; &amp; more\n</pre>\n</details>");
+
+        status.log = (0..25).map(|i| format!("line {}", i)).collect();
+        let body = render(&status, None, 100);
+        assert!(body.contains("<summary>last 20 lines of the log</summary>"));
+        assert!(body.contains("<pre>line 5\nline 6\n"));
+        assert!(body.contains("line 24\n</pre>"));
+        assert!(!body.contains("line 4\n"));
+    }
+}
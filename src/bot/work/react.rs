@@ -0,0 +1,112 @@
+use futures::{Future, Stream, future};
+use futures::sync::oneshot;
+use serde_json::{self, Value};
+use tokio_core::reactor::Handle;
+
+use bot::client::{Client, Error, ReactionKind};
+
+use super::status::JobState;
+use super::task::{Context, Task, TaskError};
+
+/// The kind of `React` tasks.
+pub const REACT: &'static str = "react";
+
+/// A comment to react to, and who the bot is there, so it can find its
+/// own reactions again.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Acknowledge {
+    pub host: String,
+    pub repo: String,
+    pub comment: u64,
+    pub username: String,
+}
+
+/// Reacts to a comment.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct React {
+    pub host: String,
+    pub repo: String,
+    pub comment: u64,
+    pub reaction: ReactionKind,
+}
+
+impl Task for React {
+    fn kind() -> &'static str {
+        REACT
+    }
+
+    fn describe(&self) -> String {
+        format!("react to a comment in {}", self.repo)
+    }
+
+    fn execute(&self, cx: &Context) -> Box<Future<Item=(), Error=TaskError>> {
+        let client = match cx.github(&self.host) {
+            Some(client) => client,
+            None => return Box::new(future::err(TaskError::fatal(format!("no github host named {:?}", self.host)))),
+        };
+        Box::new(client.react_to_comment(&self.repo, self.comment, self.reaction)
+            .map(|_| ())
+            .map_err(|e| TaskError::fatal(format!("{} ({})", e, e.category()))))
+    }
+
+    fn to_json(&self) -> Value {
+        serde_json::to_value(self).expect("reactions are plain data")
+    }
+}
+
+/// The id of the :eyes: `accepted` put on a comment, once GitHub has it.
+pub type Seen = oneshot::Receiver<u64>;
+
+/// Lets whoever asked for a job know it was taken.
+pub fn accepted(client: &Client, handle: &Handle, ack: &Acknowledge) -> Option<Seen> {
+    let client = match client.host(&ack.host) {
+        Some(client) => client,
+        None => return None,
+    };
+    let comment = ack.comment;
+    let (tx, seen) = oneshot::channel();
+    handle.spawn(client.react_to_comment(&ack.repo, comment, ReactionKind::Eyes)
+        .map(move |reaction| {
+            let _ = tx.send(reaction.id);
+        })
+        .map_err(move |e| warn!("couldn't react to comment {}: {}", comment, e)));
+    Some(seen)
+}
+
+/// Swaps the :eyes: for how the job went. With `seen` from `accepted`, that
+/// reaction is waited for and removed, so a job quicker than the reaction
+/// doesn't leave it behind; without, as for a job resumed after a restart,
+/// any :eyes: of ours is.
+pub fn finished(client: &Client, handle: &Handle, ack: Acknowledge, seen: Option<Seen>, state: JobState) {
+    let client = match client.host(&ack.host) {
+        Some(client) => client,
+        None => return,
+    };
+    let outcome = match state {
+        JobState::Succeeded => Some(ReactionKind::Rocket),
+        JobState::Failed => Some(ReactionKind::Confused),
+        _ => None,
+    };
+    let comment = ack.comment;
+    let remover = client.clone();
+    let repo = ack.repo.clone();
+    let username = ack.username;
+    let unseen: Box<Future<Item=(), Error=Error>> = match seen {
+        Some(seen) => Box::new(seen.then(move |reaction| -> Box<Future<Item=(), Error=Error>> {
+            match reaction {
+                Ok(reaction) => Box::new(remover.delete_comment_reaction(&repo, comment, reaction)),
+                // it never got there
+                Err(_) => Box::new(future::ok(())),
+            }
+        })),
+        None => Box::new(client.comment_reactions(&ack.repo, comment)
+            .filter(move |reaction| reaction.content == ReactionKind::Eyes && reaction.user.login == username)
+            .for_each(move |reaction| remover.delete_comment_reaction(&repo, comment, reaction.id))),
+    };
+    let react: Box<Future<Item=(), Error=Error>> = match outcome {
+        Some(kind) => Box::new(client.react_to_comment(&ack.repo, comment, kind).map(|_| ())),
+        None => Box::new(future::ok(())),
+    };
+    handle.spawn(unseen.and_then(move |()| react)
+        .map_err(move |e| warn!("couldn't react to comment {}: {}", comment, e)));
+}
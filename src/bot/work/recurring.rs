@@ -0,0 +1,75 @@
+use std::time::Duration;
+
+use futures::Future;
+use tokio_core::reactor::Timeout;
+
+use config::{Config, When};
+use cron;
+use super::Queue;
+
+/// Sets a timer for each of the config's `[[schedule]]` entries. Each time
+/// one fires, its job is scheduled like any other.
+///
+/// Runs missed while the bot was down are skipped, not caught up on.
+pub fn start(config: &Config, queue: &Queue) {
+    for i in 0..config.schedules().len() {
+        arm(config.clone(), i, queue.clone(), cron::now());
+    }
+}
+
+fn arm(config: Config, index: usize, queue: Queue, after: u64) {
+    let (name, next, repeats) = {
+        let schedule = &config.schedules()[index];
+        match *schedule.when() {
+            When::Cron(ref cron) => (schedule.name().to_string(), cron.next_after(after), true),
+            When::At(at) if at > after => (schedule.name().to_string(), Some(at), false),
+            When::At(_) => {
+                debug!("schedule {:?} is in the past, not running it", schedule.name());
+                return;
+            },
+        }
+    };
+    let at = match next {
+        Some(at) => at,
+        None => {
+            warn!("schedule {:?} never fires", name);
+            return;
+        }
+    };
+    let delay = Duration::from_secs(at.saturating_sub(cron::now()));
+    let timeout = match Timeout::new(delay, &queue.jobs.handle) {
+        Ok(timeout) => timeout,
+        Err(e) => {
+            error!("couldn't set a timer for schedule {:?}: {}", name, e);
+            return;
+        }
+    };
+    debug!("schedule {:?} next runs at {}", name, cron::format_timestamp(at));
+    let handle = queue.jobs.handle.clone();
+    handle.spawn(timeout.then(move |_| {
+        fire(&config, index, &queue);
+        if repeats {
+            // from the time it was due, so a timer that fires a little early
+            // doesn't run it twice
+            arm(config, index, queue, at);
+        }
+        Ok(())
+    }));
+}
+
+fn fire(config: &Config, index: usize, queue: &Queue) {
+    let schedule = &config.schedules()[index];
+    let host = match schedule.host().or_else(|| config.github_hosts().first().map(|g| g.name())) {
+        Some(host) => host.to_string(),
+        None => {
+            error!("schedule {:?} has no github host to run on", schedule.name());
+            return;
+        }
+    };
+    info!("running schedule {:?}", schedule.name());
+    let mut job = queue.new_job();
+    job.comment(host, schedule.repo().to_string(), schedule.issue(), schedule.comment().to_string());
+    if let Err(e) = queue.schedule(job) {
+        error!("couldn't schedule a run of {:?}: {:?}", schedule.name(), e);
+    }
+}
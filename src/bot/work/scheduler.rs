@@ -0,0 +1,210 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use super::Job;
+
+/// Decides which waiting job runs next.
+///
+/// Jobs that share a key, such as a repo or a deploy environment, run one at
+/// a time; a job may have several keys, and waits until none of them is busy.
+/// No more than `max_running` jobs run at once. Jobs queue up behind their
+/// first key, which take turns so one busy repo can't starve the rest, and
+/// run in order within it. Jobs without a key only count against the global
+/// limit.
+pub struct Scheduler {
+    max_running: usize,
+    running: usize,
+    busy: HashSet<String>,
+    waiting: HashMap<String, VecDeque<Job>>,
+    // first keys with waiting jobs, in the order they get their next turn
+    turns: VecDeque<String>,
+}
+
+// jobs without a key share this queue, but never block each other
+const UNKEYED: &'static str = "";
+
+impl Scheduler {
+    pub fn new(max_running: usize) -> Scheduler {
+        Scheduler {
+            max_running: max_running,
+            running: 0,
+            busy: HashSet::new(),
+            waiting: HashMap::new(),
+            turns: VecDeque::new(),
+        }
+    }
+
+    pub fn push(&mut self, job: Job) {
+        let key = job.keys().into_iter().next().unwrap_or_else(|| UNKEYED.to_string());
+        let queue = self.waiting.entry(key.clone()).or_insert_with(VecDeque::new);
+        if queue.is_empty() {
+            self.turns.push_back(key);
+        }
+        queue.push_back(job);
+    }
+
+    /// The next job that's allowed to start, if any.
+    pub fn next(&mut self) -> Option<Job> {
+        if self.running >= self.max_running {
+            return None;
+        }
+        let turn = {
+            let busy = &self.busy;
+            let waiting = &self.waiting;
+            self.turns.iter().position(|key| {
+                let first = waiting.get(key).and_then(|queue| queue.front()).expect("turns only hold waiting keys");
+                first.keys().iter().all(|key| !busy.contains(key))
+            })
+        };
+        let key = match turn {
+            Some(pos) => self.turns.remove(pos).expect("position is in bounds"),
+            None => return None,
+        };
+
+        let (job, more) = {
+            let queue = self.waiting.get_mut(&key).expect("turns only hold waiting keys");
+            let job = queue.pop_front().expect("turns only hold waiting keys");
+            (job, !queue.is_empty())
+        };
+        if more {
+            self.turns.push_back(key.clone());
+        } else {
+            self.waiting.remove(&key);
+        }
+        self.busy.extend(job.keys());
+        self.running += 1;
+        Some(job)
+    }
+
+    /// Takes job `id` back out, if it's still waiting.
+    pub fn remove(&mut self, id: u64) -> Option<Job> {
+        let found = self.waiting.iter()
+            .filter_map(|(key, queue)| queue.iter().position(|job| job.id == id).map(|pos| (key.clone(), pos)))
+            .next();
+        let (key, pos) = match found {
+            Some(found) => found,
+            None => return None,
+        };
+        let (job, empty) = {
+            let queue = self.waiting.get_mut(&key).expect("found in waiting");
+            let job = queue.remove(pos).expect("position is in bounds");
+            (job, queue.is_empty())
+        };
+        if empty {
+            self.waiting.remove(&key);
+            if let Some(pos) = self.turns.iter().position(|k| *k == key) {
+                self.turns.remove(pos);
+            }
+        }
+        Some(job)
+    }
+
+    /// A job that `next` handed out has finished.
+    pub fn done(&mut self, keys: &[String]) {
+        self.running -= 1;
+        for key in keys {
+            self.busy.remove(key);
+        }
+    }
+
+    /// How many jobs are waiting for their turn.
+    pub fn waiting(&self) -> usize {
+        self.waiting.values().map(VecDeque::len).sum()
+    }
+
+    pub fn running(&self) -> usize {
+        self.running
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Scheduler;
+    use super::super::Job;
+
+    fn job(id: u64, keys: &[&str]) -> Job {
+        let mut job = Job::new(id);
+        for key in keys {
+            job.serialize_on(key.to_string());
+        }
+        job
+    }
+
+    fn next(scheduler: &mut Scheduler) -> Option<u64> {
+        scheduler.next().map(|job| job.id())
+    }
+
+    #[test]
+    fn test_same_key_runs_in_order() {
+        let mut scheduler = Scheduler::new(4);
+        scheduler.push(job(1, &["a"]));
+        scheduler.push(job(2, &["a"]));
+        scheduler.push(job(3, &["a"]));
+        assert_eq!(next(&mut scheduler), Some(1));
+        assert_eq!(next(&mut scheduler), None);
+        scheduler.done(&["a".to_string()]);
+        assert_eq!(next(&mut scheduler), Some(2));
+        scheduler.done(&["a".to_string()]);
+        assert_eq!(next(&mut scheduler), Some(3));
+    }
+
+    #[test]
+    fn test_keys_take_turns() {
+        let mut scheduler = Scheduler::new(1);
+        for id in 1..4 {
+            scheduler.push(job(id, &["busy"]));
+        }
+        scheduler.push(job(4, &["quiet"]));
+        scheduler.push(job(5, &[]));
+        let mut order = Vec::new();
+        while let Some(job) = scheduler.next() {
+            order.push(job.id());
+            scheduler.done(&job.keys());
+        }
+        // the quiet repo and the unkeyed job don't wait for all of busy's
+        assert_eq!(order, vec![1, 4, 5, 2, 3]);
+        assert_eq!(scheduler.waiting(), 0);
+    }
+
+    #[test]
+    fn test_limits() {
+        let mut scheduler = Scheduler::new(2);
+        scheduler.push(job(1, &[]));
+        scheduler.push(job(2, &[]));
+        scheduler.push(job(3, &["a"]));
+        assert_eq!(next(&mut scheduler), Some(1));
+        assert_eq!(next(&mut scheduler), Some(3));
+        assert_eq!(next(&mut scheduler), None);
+        assert_eq!((scheduler.running(), scheduler.waiting()), (2, 1));
+        scheduler.done(&[]);
+        assert_eq!(next(&mut scheduler), Some(2));
+    }
+
+    #[test]
+    fn test_every_key_has_to_be_free() {
+        let mut scheduler = Scheduler::new(4);
+        // two repos deploying to the same environment
+        scheduler.push(job(1, &["repo-a", "deploy:prod"]));
+        scheduler.push(job(2, &["repo-b", "deploy:prod"]));
+        scheduler.push(job(3, &["repo-b"]));
+        scheduler.push(job(4, &["repo-c", "deploy:stage"]));
+        assert_eq!(next(&mut scheduler), Some(1));
+        // 2 waits for prod, and 3 waits behind 2 in its repo
+        assert_eq!(next(&mut scheduler), Some(4));
+        assert_eq!(next(&mut scheduler), None);
+        scheduler.done(&["repo-a".to_string(), "deploy:prod".to_string()]);
+        assert_eq!(next(&mut scheduler), Some(2));
+        assert_eq!(next(&mut scheduler), None);
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut scheduler = Scheduler::new(1);
+        scheduler.push(job(1, &["a"]));
+        scheduler.push(job(2, &["b"]));
+        assert_eq!(scheduler.remove(2).map(|job| job.id()), Some(2));
+        assert_eq!(scheduler.remove(2).map(|job| job.id()), None);
+        assert_eq!(next(&mut scheduler), Some(1));
+        scheduler.done(&["a".to_string()]);
+        assert_eq!(next(&mut scheduler), None);
+    }
+}
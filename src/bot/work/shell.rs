@@ -0,0 +1,347 @@
+use std::cmp;
+use std::collections::BTreeMap;
+use std::env;
+use std::io::{self, Read};
+use std::path::PathBuf;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::{Arc, mpsc as std_mpsc};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use futures::{Future, Stream, future};
+use futures::sync::{mpsc, oneshot};
+use serde_json::{self, Value};
+
+use config;
+
+use super::task::{Context, Task, TaskError};
+
+/// The kind of `Shell` tasks.
+pub const SHELL: &'static str = "shell";
+
+// how long a stopped command has to clean up before it's killed outright
+const GRACE_SECS: u64 = 10;
+// how often to check on a running command
+const POLL_MILLIS: u64 = 100;
+
+/// Runs one of the configured commands.
+///
+/// The command is copied out of the config when the task is made, so a job
+/// runs what was configured when it was asked for, even across a restart.
+/// The exception is its `env`, which may hold secrets; that's looked up by
+/// the command's name when it runs, so it's never written to the queue or
+/// dead letter files. Its output goes to the job log, and if the task is
+/// cancelled or times out, the command and everything it started get a
+/// SIGTERM, and a SIGKILL if they're still around 10 seconds later.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Shell {
+    pub name: String,
+    pub program: String,
+    pub args: Vec<String>,
+    pub dir: Option<PathBuf>,
+    pub pass_env: Vec<String>,
+    /// Seconds.
+    pub timeout: u64,
+    /// Bytes of output to keep.
+    pub max_output: usize,
+}
+
+impl Shell {
+    pub fn new(command: &config::Command) -> Shell {
+        Shell {
+            name: command.name().to_string(),
+            program: command.program().to_string(),
+            args: command.args().to_vec(),
+            dir: command.dir().map(|dir| dir.to_path_buf()),
+            pass_env: command.pass_env().to_vec(),
+            timeout: command.timeout(),
+            max_output: command.max_output(),
+        }
+    }
+
+    fn command(&self, vars: &BTreeMap<String, String>) -> Command {
+        let mut cmd = Command::new(&self.program);
+        cmd.args(&self.args)
+            .env_clear()
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        for name in &self.pass_env {
+            if let Some(value) = env::var_os(name) {
+                cmd.env(name, value);
+            }
+        }
+        for (name, value) in vars {
+            cmd.env(name, value);
+        }
+        if let Some(ref dir) = self.dir {
+            cmd.current_dir(dir);
+        }
+        own_process_group(&mut cmd);
+        cmd
+    }
+}
+
+impl Task for Shell {
+    fn kind() -> &'static str {
+        SHELL
+    }
+
+    fn describe(&self) -> String {
+        format!("run {}", self.name)
+    }
+
+    fn execute(&self, cx: &Context) -> Box<Future<Item=(), Error=TaskError>> {
+        let vars = match cx.config().command(&self.name) {
+            Some(command) => command.env().clone(),
+            None => {
+                return Box::new(future::err(TaskError::fatal(format!("{} isn't configured any more", self.name))));
+            }
+        };
+        let mut child = match self.command(&vars).spawn() {
+            Ok(child) => child,
+            Err(e) => {
+                error!("couldn't start {:?}: {}", self.program, e);
+                return Box::new(future::err(TaskError::fatal(format!("couldn't start {}: {}", self.name, e))));
+            }
+        };
+        cx.log(format!("$ {} {}", self.program, self.args.join(" ")));
+        let (stop, stopped) = std_mpsc::channel();
+        let group = Group {
+            stop: stop,
+            done: false,
+        };
+
+        // the pipes are read on threads of their own, and the lines sent back
+        // to the core for the job log
+        let (lines, output) = mpsc::unbounded();
+        let budget = Arc::new(AtomicUsize::new(self.max_output));
+        let mut readers = Vec::new();
+        if let Some(stdout) = child.stdout.take() {
+            readers.push(forward(stdout, "", lines.clone(), budget.clone()));
+        }
+        if let Some(stderr) = child.stderr.take() {
+            readers.push(forward(stderr, "stderr: ", lines.clone(), budget.clone()));
+        }
+        drop(lines);
+
+        let (exited, exit) = oneshot::channel();
+        thread::spawn(move || {
+            let status = supervise(&mut child, &stopped);
+            for reader in readers {
+                let _ = reader.join();
+            }
+            let _ = exited.send(status);
+        });
+
+        let log = cx.clone();
+        let note = cx.clone();
+        let max_output = self.max_output;
+        let name = self.name.clone();
+        Box::new(output.for_each(move |line| {
+            log.log(line);
+            Ok(())
+        }).then(move |_| exit).then(move |status| {
+            let mut group = group;
+            group.done = true;
+            if budget.load(Ordering::SeqCst) == 0 {
+                note.log(format!("(output cut off after {} bytes)", max_output));
+            }
+            match status {
+                Ok(Ok(status)) => exit_status(&name, status),
+                Ok(Err(e)) => Err(TaskError::fatal(format!("couldn't wait for {}: {}", name, e))),
+                Err(_) => Err(TaskError::fatal(format!("lost track of {}", name))),
+            }
+        }))
+    }
+
+    fn to_json(&self) -> Value {
+        serde_json::to_value(self).expect("commands are plain data")
+    }
+
+    fn timeout(&self) -> Option<Duration> {
+        Some(Duration::from_secs(self.timeout))
+    }
+}
+
+fn exit_status(name: &str, status: ExitStatus) -> Result<(), TaskError> {
+    if status.success() {
+        Ok(())
+    } else {
+        Err(TaskError::fatal(format!("{} failed ({})", name, status)))
+    }
+}
+
+// Sends each line of `pipe` to `lines` until `budget` bytes have been kept,
+// then keeps reading so the command doesn't block on a full pipe. Only what
+// fits in the budget is ever held, however long the lines are.
+fn forward<R>(pipe: R, prefix: &'static str, lines: mpsc::UnboundedSender<String>, budget: Arc<AtomicUsize>)
+    -> thread::JoinHandle<()>
+    where R: Read + Send + 'static,
+{
+    thread::spawn(move || {
+        let mut pipe = pipe;
+        let mut chunk = [0; 4096];
+        // the start of a line whose end hasn't been read yet
+        let mut line = Vec::new();
+        // if nobody's listening any more, keep draining the pipe anyway
+        let send = |line: &[u8]| {
+            let _ = lines.send(format!("{}{}", prefix, String::from_utf8_lossy(line)));
+        };
+        loop {
+            let n = match pipe.read(&mut chunk) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(_) => break,
+            };
+            let kept = take(&budget, n);
+            line.extend_from_slice(&chunk[..kept]);
+            while let Some(end) = line.iter().position(|&b| b == b'\n') {
+                send(&line[..end]);
+                line.drain(..end + 1);
+            }
+        }
+        if !line.is_empty() {
+            send(&line);
+        }
+    })
+}
+
+// Takes up to `n` bytes from `budget`, and says how many it got.
+fn take(budget: &AtomicUsize, n: usize) -> usize {
+    let mut left = budget.load(Ordering::SeqCst);
+    loop {
+        let got = cmp::min(left, n);
+        if got == 0 {
+            return 0;
+        }
+        let was = budget.compare_and_swap(left, left - got, Ordering::SeqCst);
+        if was == left {
+            return got;
+        }
+        left = was;
+    }
+}
+
+// The command and everything it starts, stopped if the task is dropped
+// before the command exits, as when it's cancelled or times out.
+struct Group {
+    stop: std_mpsc::Sender<()>,
+    done: bool,
+}
+
+impl Drop for Group {
+    fn drop(&mut self) {
+        if !self.done {
+            let _ = self.stop.send(());
+        }
+    }
+}
+
+// Waits for `child` to exit, stopping its process group when asked. Only the
+// thread that owns the child signals the group, and only before reaping it,
+// so the signal can't reach some other process that got the id since.
+fn supervise(child: &mut Child, stop: &std_mpsc::Receiver<()>) -> io::Result<ExitStatus> {
+    let pid = child.id();
+    let poll = Duration::from_millis(POLL_MILLIS);
+    // when to give up on it cleaning up after itself
+    let mut deadline = None;
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(status);
+        }
+        match deadline {
+            None => match stop.recv_timeout(poll) {
+                Err(std_mpsc::RecvTimeoutError::Timeout) => {},
+                // asked to, or the task's gone without saying
+                Ok(()) | Err(std_mpsc::RecvTimeoutError::Disconnected) => {
+                    warn!("stopping process group {}", pid);
+                    signal_process_group(pid, Signal::Terminate);
+                    deadline = Some(Instant::now() + Duration::from_secs(GRACE_SECS));
+                },
+            },
+            Some(at) if Instant::now() >= at => {
+                warn!("killing process group {}", pid);
+                signal_process_group(pid, Signal::Kill);
+                return child.wait();
+            },
+            Some(_) => thread::sleep(poll),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Signal {
+    Terminate,
+    Kill,
+}
+
+#[cfg(unix)]
+fn own_process_group(cmd: &mut Command) {
+    use std::io;
+    use std::os::unix::process::CommandExt;
+    use libc;
+    cmd.before_exec(|| {
+        if unsafe { libc::setpgid(0, 0) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    });
+}
+
+#[cfg(not(unix))]
+fn own_process_group(_cmd: &mut Command) {}
+
+#[cfg(unix)]
+fn signal_process_group(pid: u32, signal: Signal) {
+    use libc;
+    let signal = match signal {
+        Signal::Terminate => libc::SIGTERM,
+        Signal::Kill => libc::SIGKILL,
+    };
+    unsafe {
+        libc::kill(-(pid as libc::pid_t), signal);
+    }
+}
+
+#[cfg(not(unix))]
+fn signal_process_group(pid: u32, signal: Signal) {
+    error!("can't send {:?} to process {} on this platform", signal, pid);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use futures::{Future, Stream};
+    use futures::sync::mpsc;
+
+    use super::forward;
+
+    fn output(text: &[u8], max_output: usize) -> (Vec<String>, usize) {
+        let (tx, rx) = mpsc::unbounded();
+        let budget = Arc::new(AtomicUsize::new(max_output));
+        forward(Cursor::new(text.to_vec()), "", tx, budget.clone()).join().unwrap();
+        (rx.collect().wait().unwrap(), budget.load(Ordering::SeqCst))
+    }
+
+    #[test]
+    fn test_output_is_cut_off_at_the_budget() {
+        assert_eq!(output(b"one\ntwo\nthree\n", 100), (vec!["one".to_string(), "two".to_string(), "three".to_string()], 86));
+        // the last line is cut short rather than going over
+        assert_eq!(output(b"one\ntwo\nthree\n", 9), (vec!["one".to_string(), "two".to_string(), "t".to_string()], 0));
+        assert_eq!(output(b"no newline", 100), (vec!["no newline".to_string()], 90));
+    }
+
+    #[test]
+    fn test_long_lines_are_not_held_whole() {
+        let long = vec![b'x'; 1 << 20];
+        let (lines, left) = output(&long, 10);
+        assert_eq!(lines, vec!["x".repeat(10)]);
+        assert_eq!(left, 0);
+    }
+}
@@ -0,0 +1,190 @@
+use std::collections::{BTreeMap, VecDeque};
+
+use cron::now;
+use super::{Job, Origin};
+
+/// Where a job is in its life.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub enum JobState {
+    #[serde(rename = "queued")]
+    Queued,
+    #[serde(rename = "running")]
+    Running,
+    #[serde(rename = "succeeded")]
+    Succeeded,
+    #[serde(rename = "failed")]
+    Failed,
+    #[serde(rename = "cancelled")]
+    Cancelled,
+}
+
+/// What became of one task in a job.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub enum TaskState {
+    #[serde(rename = "pending")]
+    Pending,
+    #[serde(rename = "running")]
+    Running,
+    #[serde(rename = "succeeded")]
+    Succeeded,
+    #[serde(rename = "failed")]
+    Failed,
+    /// An earlier task failed, or the job was cancelled, so this one
+    /// never ran.
+    #[serde(rename = "skipped")]
+    Skipped,
+    /// The job was cancelled while this task was running.
+    #[serde(rename = "cancelled")]
+    Cancelled,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct TaskStatus {
+    pub task: String,
+    pub state: TaskState,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub started_at: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<u64>,
+}
+
+/// A job as the outside world sees it. Times are seconds since the epoch.
+#[derive(Clone, Debug, Serialize)]
+pub struct JobStatus {
+    pub id: u64,
+    pub state: JobState,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub origin: Option<Origin>,
+    pub queued_at: u64,
+    /// When a delayed job is due to start.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub run_at: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub started_at: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<u64>,
+    pub tasks: Vec<TaskStatus>,
+    /// What the job's tasks had to say while they ran, such as a command's
+    /// output.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub log: Vec<String>,
+}
+
+/// Every unfinished job, plus the last `limit` finished ones.
+pub struct History {
+    limit: usize,
+    jobs: BTreeMap<u64, JobStatus>,
+    // finished jobs, oldest first, so they can be forgotten in order
+    finished: VecDeque<u64>,
+}
+
+impl History {
+    pub fn new(limit: usize) -> History {
+        History {
+            limit: limit,
+            jobs: BTreeMap::new(),
+            finished: VecDeque::new(),
+        }
+    }
+
+    pub fn queued(&mut self, job: &Job) {
+        let tasks = job.tasks.iter().enumerate().map(|(i, task)| {
+            TaskStatus {
+                task: task.description.clone(),
+                // tasks a resumed or replayed job already got through
+                state: if i < job.done || job.succeeded.contains(&i) {
+                    TaskState::Succeeded
+                } else {
+                    TaskState::Pending
+                },
+                error: None,
+                started_at: None,
+                finished_at: None,
+            }
+        }).collect();
+        self.jobs.insert(job.id, JobStatus {
+            id: job.id,
+            state: JobState::Queued,
+            description: job.description.clone(),
+            origin: job.origin.clone(),
+            queued_at: now(),
+            run_at: job.run_at,
+            started_at: None,
+            finished_at: None,
+            tasks: tasks,
+            log: Vec::new(),
+        });
+    }
+
+    pub fn started(&mut self, id: u64) {
+        if let Some(status) = self.jobs.get_mut(&id) {
+            status.state = JobState::Running;
+            status.started_at = Some(now());
+        }
+    }
+
+    pub fn task_started(&mut self, id: u64, index: usize) {
+        let task = self.jobs.get_mut(&id).and_then(|status| status.tasks.get_mut(index));
+        if let Some(task) = task {
+            task.state = TaskState::Running;
+            task.started_at = Some(now());
+        }
+    }
+
+    pub fn task(&mut self, id: u64, index: usize, state: TaskState, error: Option<String>) {
+        let task = self.jobs.get_mut(&id).and_then(|status| status.tasks.get_mut(index));
+        if let Some(task) = task {
+            task.state = state;
+            task.error = error;
+            task.finished_at = Some(now());
+        }
+    }
+
+    pub fn log(&mut self, id: u64, line: String) {
+        if let Some(status) = self.jobs.get_mut(&id) {
+            status.log.push(line);
+        }
+    }
+
+    pub fn finished(&mut self, id: u64, state: JobState) {
+        if let Some(status) = self.jobs.get_mut(&id) {
+            status.state = state;
+            status.finished_at = Some(now());
+        } else {
+            return;
+        }
+        self.finished.push_back(id);
+        while self.finished.len() > self.limit {
+            if let Some(old) = self.finished.pop_front() {
+                self.jobs.remove(&old);
+            }
+        }
+    }
+
+    pub fn get(&self, id: u64) -> Option<&JobStatus> {
+        self.jobs.get(&id)
+    }
+
+    /// The newest unfinished job asked for by `requester` on `host`, leaving
+    /// out delayed jobs such as reminders.
+    pub fn latest_unfinished(&self, host: &str, requester: &str) -> Option<u64> {
+        self.jobs.values().rev()
+            .filter(|status| status.state == JobState::Queued || status.state == JobState::Running)
+            .filter(|status| status.run_at.is_none())
+            .filter(|status| {
+                status.origin.as_ref()
+                    .map_or(false, |origin| origin.host == host && origin.requester == requester)
+            })
+            .map(|status| status.id)
+            .next()
+    }
+
+    /// Newest first.
+    pub fn list(&self) -> Vec<&JobStatus> {
+        self.jobs.values().rev().collect()
+    }
+}
@@ -0,0 +1,237 @@
+use std::cmp;
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+use serde_json;
+
+use super::Job;
+
+// the log is compacted once it has this many lines...
+const COMPACT_MIN_RECORDS: usize = 1000;
+// ...and this many times as many lines as unfinished jobs
+const COMPACT_RATIO: usize = 4;
+
+/// Where the queue remembers jobs, so they can be picked back up after a
+/// restart.
+pub trait Store {
+    /// Every job that was scheduled but never finished, oldest first, and
+    /// the id to give the next new job.
+    fn recover(&mut self) -> io::Result<Recovered>;
+    fn scheduled(&mut self, job: &Job) -> io::Result<()>;
+    /// The first `done` tasks of job `id` have completed.
+    fn progress(&mut self, id: u64, done: usize) -> io::Result<()>;
+    /// Job `id` posted its progress comment as `comment`, which a resumed
+    /// job goes on editing instead of posting another.
+    fn commented(&mut self, id: u64, comment: u64) -> io::Result<()>;
+    /// Job `id` won't run any more tasks, whether it succeeded or not.
+    fn finished(&mut self, id: u64) -> io::Result<()>;
+}
+
+pub struct Recovered {
+    pub jobs: Vec<Job>,
+    pub next_id: u64,
+}
+
+/// Keeps nothing; jobs are lost when the process exits.
+pub struct MemoryStore;
+
+impl Store for MemoryStore {
+    fn recover(&mut self) -> io::Result<Recovered> {
+        Ok(Recovered {
+            jobs: Vec::new(),
+            next_id: 1,
+        })
+    }
+
+    fn scheduled(&mut self, _job: &Job) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn progress(&mut self, _id: u64, _done: usize) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn commented(&mut self, _id: u64, _comment: u64) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn finished(&mut self, _id: u64) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+enum Entry {
+    // written first when compacting, so ids aren't reused
+    NextId(u64),
+    Scheduled(Job),
+    Progress {
+        id: u64,
+        done: usize,
+    },
+    Commented {
+        id: u64,
+        comment: u64,
+    },
+    Finished {
+        id: u64,
+    },
+}
+
+/// An append-only log of JSON lines, one per change to a job.
+///
+/// The log is compacted down to just the unfinished jobs when it's
+/// recovered, and again whenever finished jobs make up most of it.
+pub struct LogStore {
+    path: PathBuf,
+    file: File,
+    // lines in the log, and how many jobs in it are unfinished
+    records: usize,
+    live: usize,
+}
+
+impl LogStore {
+    pub fn open<P: Into<PathBuf>>(path: P) -> io::Result<LogStore> {
+        let path = path.into();
+        let file = LogStore::append(&path)?;
+        let mut store = LogStore {
+            path: path,
+            file: file,
+            records: 0,
+            live: 0,
+        };
+        let (jobs, _) = store.replay()?;
+        store.live = jobs.len();
+        store.records = BufReader::new(File::open(&store.path)?).lines().count();
+        Ok(store)
+    }
+
+    fn append(path: &Path) -> io::Result<File> {
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+    }
+
+    fn replay(&self) -> io::Result<(BTreeMap<u64, Job>, u64)> {
+        let mut jobs = BTreeMap::new();
+        let mut next_id = 1;
+        let reader = BufReader::new(File::open(&self.path)?);
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            // a crash mid-write can leave the last line cut short
+            let entry = match serde_json::from_str(&line) {
+                Ok(entry) => entry,
+                Err(e) => {
+                    warn!("skipping bad line {} of {:?}: {}", i + 1, self.path, e);
+                    continue;
+                }
+            };
+            match entry {
+                Entry::NextId(id) => {
+                    next_id = cmp::max(next_id, id);
+                },
+                Entry::Scheduled(job) => {
+                    next_id = cmp::max(next_id, job.id + 1);
+                    jobs.insert(job.id, job);
+                },
+                Entry::Progress { id, done } => {
+                    if let Some(job) = jobs.get_mut(&id) {
+                        job.done = done;
+                    }
+                },
+                Entry::Commented { id, comment } => {
+                    if let Some(progress) = jobs.get_mut(&id).and_then(|job| job.progress.as_mut()) {
+                        progress.comment = Some(comment);
+                    }
+                },
+                Entry::Finished { id } => {
+                    jobs.remove(&id);
+                },
+            }
+        }
+        Ok((jobs, next_id))
+    }
+
+    fn write(&mut self, entry: &Entry) -> io::Result<()> {
+        let line = serde_json::to_string(entry)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        self.file.write_all(line.as_bytes())?;
+        self.file.write_all(b"\n")?;
+        self.file.sync_data()?;
+        self.records += 1;
+        match *entry {
+            Entry::Scheduled(_) => self.live += 1,
+            Entry::Finished { .. } => self.live = self.live.saturating_sub(1),
+            _ => {},
+        }
+        if self.records >= COMPACT_MIN_RECORDS && self.records > (self.live + 1) * COMPACT_RATIO {
+            self.compact()?;
+        }
+        Ok(())
+    }
+
+    // rewrites the log as just the unfinished jobs
+    fn compact(&mut self) -> io::Result<(BTreeMap<u64, Job>, u64)> {
+        let (jobs, next_id) = self.replay()?;
+        debug!("compacting {:?} from {} lines to {}", self.path, self.records, jobs.len() + 1);
+
+        let tmp = self.path.with_extension("compact");
+        {
+            let mut file = File::create(&tmp)?;
+            let entries = Some(Entry::NextId(next_id)).into_iter()
+                .chain(jobs.values().map(|job| Entry::Scheduled(job.clone())));
+            for entry in entries {
+                let line = serde_json::to_string(&entry)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                file.write_all(line.as_bytes())?;
+                file.write_all(b"\n")?;
+            }
+            file.sync_all()?;
+        }
+        fs::rename(&tmp, &self.path)?;
+        self.file = LogStore::append(&self.path)?;
+        self.records = jobs.len() + 1;
+        self.live = jobs.len();
+        Ok((jobs, next_id))
+    }
+}
+
+impl Store for LogStore {
+    fn recover(&mut self) -> io::Result<Recovered> {
+        let (jobs, next_id) = self.compact()?;
+        Ok(Recovered {
+            jobs: jobs.into_iter().map(|(_, job)| job).collect(),
+            next_id: next_id,
+        })
+    }
+
+    fn scheduled(&mut self, job: &Job) -> io::Result<()> {
+        self.write(&Entry::Scheduled(job.clone()))
+    }
+
+    fn progress(&mut self, id: u64, done: usize) -> io::Result<()> {
+        self.write(&Entry::Progress {
+            id: id,
+            done: done,
+        })
+    }
+
+    fn commented(&mut self, id: u64, comment: u64) -> io::Result<()> {
+        self.write(&Entry::Commented {
+            id: id,
+            comment: comment,
+        })
+    }
+
+    fn finished(&mut self, id: u64) -> io::Result<()> {
+        self.write(&Entry::Finished {
+            id: id,
+        })
+    }
+}
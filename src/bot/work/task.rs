@@ -0,0 +1,243 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+use std::time::Duration;
+
+use futures::{Future, future};
+use serde::Deserialize;
+use serde_json::{self, Value};
+use tokio_core::reactor::{Handle, Timeout};
+
+use bot::client::Client;
+use bot::client::backoff;
+use config::Config;
+
+use super::status::History;
+
+/// One step of a job.
+///
+/// Tasks are kept as JSON between being scheduled and being run, so a job
+/// can be persisted and picked back up after a restart. A kind of task is
+/// written out with `to_json`, and read back in once it's been registered
+/// with `Queue::register`.
+pub trait Task {
+    /// Names this kind of task, in persisted jobs and to the registry. Must
+    /// be unique among registered kinds.
+    fn kind() -> &'static str where Self: Sized;
+
+    /// A few words on what the task does, for status pages and reports.
+    fn describe(&self) -> String;
+
+    /// Does the work.
+    fn execute(&self, cx: &Context) -> Box<Future<Item=(), Error=TaskError>>;
+
+    /// The task as JSON, to be read back in by its kind's decoder.
+    fn to_json(&self) -> Value;
+
+    /// How often to try again after a retryable error. Never, by default.
+    fn retry_policy(&self) -> RetryPolicy {
+        RetryPolicy::never()
+    }
+
+    /// How long one attempt may take before it counts as failed.
+    fn timeout(&self) -> Option<Duration> {
+        None
+    }
+}
+
+/// What a task gets to work with.
+#[derive(Clone)]
+pub struct Context {
+    client: Client,
+    handle: Handle,
+    job: u64,
+    history: Rc<RefCell<History>>,
+}
+
+impl Context {
+    pub fn new(client: Client, handle: Handle, job: u64, history: Rc<RefCell<History>>) -> Context {
+        Context {
+            client: client,
+            handle: handle,
+            job: job,
+            history: history,
+        }
+    }
+
+    /// A client for the named github host.
+    pub fn github(&self, host: &str) -> Option<Client> {
+        self.client.host(host)
+    }
+
+    pub fn handle(&self) -> &Handle {
+        &self.handle
+    }
+
+    /// The config the bot is running with now, which may be newer than
+    /// the one the job was made under.
+    pub fn config(&self) -> &Config {
+        self.client.config()
+    }
+
+    /// The id of the job the task belongs to.
+    pub fn job(&self) -> u64 {
+        self.job
+    }
+
+    /// Adds a line to the job's log, which `/jobs/{id}` shows.
+    pub fn log(&self, line: String) {
+        debug!("job {}: {}", self.job, line);
+        self.history.borrow_mut().log(self.job, line);
+    }
+}
+
+/// Why a task failed, in words fit for the issue the job came from.
+#[derive(Clone, Debug)]
+pub struct TaskError {
+    message: String,
+    retryable: bool,
+}
+
+impl TaskError {
+    /// An error that trying again won't fix.
+    pub fn fatal<S: Into<String>>(message: S) -> TaskError {
+        TaskError {
+            message: message.into(),
+            retryable: false,
+        }
+    }
+
+    /// An error that might go away, like a timeout or an outage.
+    pub fn retryable<S: Into<String>>(message: S) -> TaskError {
+        TaskError {
+            message: message.into(),
+            retryable: true,
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn is_retryable(&self) -> bool {
+        self.retryable
+    }
+}
+
+impl fmt::Display for TaskError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+/// How many times to attempt a task whose errors are retryable. Attempts
+/// back off the same way GitHub requests do.
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+}
+
+impl RetryPolicy {
+    pub fn never() -> RetryPolicy {
+        RetryPolicy::attempts(1)
+    }
+
+    /// At most `max_attempts` tries in all, counting the first.
+    pub fn attempts(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: if max_attempts == 0 { 1 } else { max_attempts },
+        }
+    }
+
+    pub fn max_attempts(&self) -> u32 {
+        self.max_attempts
+    }
+}
+
+/// A task as it's kept in a job: its kind, and the JSON to rebuild it from.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TaskData {
+    pub kind: String,
+    pub description: String,
+    pub data: Value,
+}
+
+impl TaskData {
+    pub fn new<T: Task>(task: &T) -> TaskData {
+        TaskData {
+            kind: T::kind().to_string(),
+            description: task.describe(),
+            data: task.to_json(),
+        }
+    }
+}
+
+/// Turns task JSON back into tasks.
+pub type Decoder = Box<Fn(Value) -> Result<Box<Task>, String>>;
+
+/// Every kind of task a queue knows how to run.
+pub struct Registry {
+    decoders: HashMap<String, Decoder>,
+}
+
+impl Registry {
+    pub fn new() -> Registry {
+        Registry {
+            decoders: HashMap::new(),
+        }
+    }
+
+    /// Decodes tasks of `T`'s kind by deserializing them as a `T`.
+    pub fn register<T: Task + Deserialize + 'static>(&mut self) {
+        self.decoders.insert(T::kind().to_string(), Box::new(move |data| {
+            serde_json::from_value::<T>(data)
+                .map(|task| Box::new(task) as Box<Task>)
+                .map_err(|e| format!("bad {} task: {}", T::kind(), e))
+        }));
+    }
+
+    pub fn decode(&self, data: &TaskData) -> Result<Box<Task>, String> {
+        match self.decoders.get(&data.kind) {
+            Some(decode) => decode(data.data.clone()),
+            None => Err(format!("unknown kind of task {:?}", data.kind)),
+        }
+    }
+}
+
+/// Runs `task`, minding its timeout and retrying as its policy allows.
+pub fn execute(task: Rc<Box<Task>>, cx: Context) -> Box<Future<Item=(), Error=TaskError>> {
+    attempt(task, cx, 1)
+}
+
+fn attempt(task: Rc<Box<Task>>, cx: Context, n: u32) -> Box<Future<Item=(), Error=TaskError>> {
+    let run = match task.timeout() {
+        Some(limit) => with_timeout(task.execute(&cx), limit, cx.handle()),
+        None => task.execute(&cx),
+    };
+    Box::new(run.or_else(move |e| -> Box<Future<Item=(), Error=TaskError>> {
+        if !e.is_retryable() || n >= task.retry_policy().max_attempts() {
+            return Box::new(future::err(e));
+        }
+        let delay = backoff(n - 1);
+        warn!("{} failed (attempt {}), retrying in {:?}: {}", task.describe(), n, delay, e);
+        match Timeout::new(delay, cx.handle()) {
+            Ok(sleep) => Box::new(sleep.then(move |_| attempt(task, cx, n + 1))),
+            Err(_) => Box::new(future::err(e)),
+        }
+    }))
+}
+
+fn with_timeout(run: Box<Future<Item=(), Error=TaskError>>, limit: Duration, handle: &Handle)
+    -> Box<Future<Item=(), Error=TaskError>>
+{
+    let timeout = match Timeout::new(limit, handle) {
+        Ok(timeout) => timeout,
+        Err(e) => return Box::new(future::err(TaskError::retryable(format!("couldn't set a timeout: {}", e)))),
+    };
+    let timed_out = timeout.then(move |_| {
+        Err(TaskError::retryable(format!("timed out after {}s", limit.as_secs())))
+    });
+    // dropping the loser is what stops a task that ran too long
+    Box::new(run.select(timed_out).map(|_| ()).map_err(|(e, _)| e))
+}
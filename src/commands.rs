@@ -0,0 +1,100 @@
+//! The commands the bot answers to. Parsing, `help`, typo suggestions and
+//! the config's aliases all go by this one table.
+
+/// Which command a line asks for. What follows its name is up to the parser.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Kind {
+    Ping,
+    Deploy,
+    Cancel,
+    Remind,
+    Reminders,
+    Forget,
+    Help,
+}
+
+pub struct Command {
+    pub kind: Kind,
+    /// What it's called, as in `@fxabot deploy` or `quiet = ["deploy"]`.
+    pub name: &'static str,
+    /// How to use it, for `help`.
+    pub usage: &'static str,
+    /// Whether anything may follow the name; if not, a line with more after
+    /// it isn't understood.
+    pub args: bool,
+    pub summary: &'static str,
+}
+
+/// In the order `help` lists them.
+pub const COMMANDS: &'static [Command] = &[
+    Command {
+        kind: Kind::Ping,
+        name: "ping",
+        usage: "ping",
+        args: false,
+        summary: "check that I'm listening",
+    },
+    Command {
+        kind: Kind::Deploy,
+        name: "deploy",
+        usage: "deploy [target]",
+        args: true,
+        summary: "run the deploy command, or the one for `target`",
+    },
+    Command {
+        kind: Kind::Cancel,
+        name: "cancel",
+        usage: "cancel [job]",
+        args: true,
+        summary: "stop a job, or your latest one",
+    },
+    Command {
+        kind: Kind::Remind,
+        name: "remind",
+        usage: "remind me <when> to <what>",
+        args: true,
+        summary: "like `remind me tomorrow 9am to ship it`",
+    },
+    Command {
+        kind: Kind::Reminders,
+        name: "reminders",
+        usage: "reminders",
+        args: false,
+        summary: "list your reminders",
+    },
+    Command {
+        kind: Kind::Forget,
+        name: "forget",
+        usage: "forget <reminder>",
+        args: true,
+        summary: "cancel a reminder",
+    },
+    Command {
+        kind: Kind::Help,
+        name: "help",
+        usage: "help",
+        args: false,
+        summary: "this",
+    },
+];
+
+/// The command called `name`, if there is one.
+pub fn find(name: &str) -> Option<&'static Command> {
+    COMMANDS.iter().find(|command| command.name == name)
+}
+
+/// What `kind` is called.
+pub fn name(kind: Kind) -> &'static str {
+    COMMANDS.iter().find(|command| command.kind == kind).map_or("", |command| command.name)
+}
+
+pub fn names() -> Vec<&'static str> {
+    COMMANDS.iter().map(|command| command.name).collect()
+}
+
+/// One line per command, each starting with a newline.
+pub fn help() -> String {
+    COMMANDS.iter()
+        .map(|command| format!("\n- `{}`: {}", command.usage, command.summary))
+        .collect()
+}
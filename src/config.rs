@@ -1,14 +1,34 @@
+use std::collections::BTreeMap;
+use std::env;
 use std::fs::File;
 use std::io::{self, Read};
 use std::net::{IpAddr, SocketAddr};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::sync::Arc;
 
+use serde::{Deserialize, Deserializer};
+use serde::de::Error as DeError;
 use toml;
 
+use commands;
+use cron::{self, Cron};
+
 // should configs ever big bigger than 50mb?
 const MAX_CONFIG_FILE_SIZE: u64 = 1024 * 1024 * 50;
+const DEFAULT_CACHE_BYTES: usize = 1024 * 1024 * 8;
+const DEFAULT_CACHE_DISK_BYTES: u64 = 1024 * 1024 * 64;
+const DEFAULT_QUEUE_CAPACITY: usize = 256;
+const DEFAULT_MAX_DELAYED: usize = 1024;
+const DEFAULT_MAX_RUNNING: usize = 4;
+const DEFAULT_RETRY_AFTER_SECS: u64 = 60;
+const DEFAULT_HISTORY: usize = 100;
+// heroku gives 30 seconds between SIGTERM and SIGKILL
+const DEFAULT_SHUTDOWN_GRACE_SECS: u64 = 25;
+const DEFAULT_COMMAND_TIMEOUT_SECS: u64 = 60 * 10;
+const DEFAULT_COMMAND_MAX_OUTPUT: usize = 1024 * 1024;
+/// The api url of github.com, used when no host says otherwise.
+pub const DEFAULT_GITHUB_API: &'static str = "https://api.github.com";
 
 #[derive(Clone, Debug)]
 pub struct Config(Arc<Inner>);
@@ -16,23 +36,171 @@ pub struct Config(Arc<Inner>);
 
 #[derive(Debug, Deserialize)]
 pub struct Inner {
-    github: Option<Github>,
+    #[serde(default, deserialize_with = "one_or_many")]
+    github: Vec<Github>,
     server: Server,
+    cache: Option<Cache>,
+    http: Option<Http>,
+    queue: Option<Queue>,
+    #[serde(default)]
+    schedule: Vec<Schedule>,
+    reminders: Option<Reminders>,
+    #[serde(default)]
+    command: Vec<Command>,
+    #[serde(default)]
+    repo: Vec<Repo>,
+    #[serde(default)]
+    aliases: BTreeMap<String, String>,
 }
 
+/// One GitHub (or GitHub Enterprise) host the bot talks to.
 #[derive(Debug, Deserialize)]
-struct Github {
+pub struct Github {
+    name: Option<String>,
+    route: Option<String>,
     username: String,
     authorized: Vec<String>,
+    #[serde(default)]
+    admins: Vec<String>,
     api: Option<String>,
+    graphql: Option<String>,
     token: Option<String>,
     webhook_secret: Option<String>,
+    mentions: Option<Mentions>,
+}
+
+// where in a comment a mention counts as a command
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+enum Mentions {
+    #[serde(rename = "start")]
+    Start,
+    #[serde(rename = "anywhere")]
+    Anywhere,
+}
+
+/// A job that runs on a timer rather than because of a webhook, such as a
+/// weekly reminder comment.
+#[derive(Debug, Deserialize)]
+pub struct Schedule {
+    name: String,
+    cron: Option<String>,
+    at: Option<String>,
+    host: Option<String>,
+    repo: String,
+    issue: u64,
+    comment: String,
+    // parsed from `cron` or `at` once the whole config is read
+    #[serde(skip_deserializing)]
+    when: Option<When>,
+}
+
+/// How the bot behaves in one repo.
+#[derive(Debug, Deserialize)]
+pub struct Repo {
+    name: String,
+    host: Option<String>,
+    #[serde(default)]
+    reactions: bool,
+    #[serde(default)]
+    quiet: Vec<String>,
+    #[serde(default)]
+    slash_commands: bool,
+}
+
+/// A program the bot may run, such as a deploy script. It's run directly,
+/// not through a shell, with only the environment given here.
+#[derive(Debug, Deserialize)]
+pub struct Command {
+    name: String,
+    program: String,
+    #[serde(default)]
+    args: Vec<String>,
+    dir: Option<PathBuf>,
+    // set for the command; looked up when it runs, so they stay out of the
+    // queue file
+    #[serde(default)]
+    env: BTreeMap<String, String>,
+    // copied from the bot's own environment when the command starts
+    #[serde(default)]
+    pass_env: Vec<String>,
+    timeout: Option<u64>,
+    max_output: Option<usize>,
+}
+
+/// When a scheduled job runs.
+#[derive(Clone, Debug)]
+pub enum When {
+    /// Every time the cron schedule fires, in UTC.
+    Cron(Cron),
+    /// Once, at this many seconds since the epoch.
+    At(u64),
+}
+
+// `[github]` for a single host, `[[github]]` for several
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum OneOrMany<T> {
+    One(T),
+    Many(Vec<T>),
+}
+
+fn one_or_many<T: Deserialize, D: Deserializer>(de: D) -> Result<Vec<T>, D::Error> {
+    OneOrMany::deserialize(de).map(|v| match v {
+        OneOrMany::One(t) => vec![t],
+        OneOrMany::Many(ts) => ts,
+    })
+}
+
+#[derive(Debug, Deserialize)]
+struct Cache {
+    max_bytes: Option<usize>,
+    dir: Option<PathBuf>,
+    max_disk_bytes: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Http {
+    proxy: Option<String>,
+    no_proxy: Option<Vec<String>>,
+    proxy_from_env: Option<bool>,
+    #[serde(default)]
+    ca_files: Vec<PathBuf>,
+    client_cert: Option<PathBuf>,
+    client_cert_password: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Queue {
+    path: Option<PathBuf>,
+    dead_letter_path: Option<PathBuf>,
+    capacity: Option<usize>,
+    max_delayed: Option<usize>,
+    max_running: Option<usize>,
+    retry_after: Option<u64>,
+    on_failure: Option<OnFailure>,
+    history: Option<usize>,
+}
+
+// what a job does with its remaining tasks once one fails
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+enum OnFailure {
+    #[serde(rename = "abort")]
+    Abort,
+    #[serde(rename = "continue")]
+    Continue,
+}
+
+#[derive(Debug, Deserialize)]
+struct Reminders {
+    timezone: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
 struct Server {
     host: IpAddr,
     port: Option<u16>,
+    admin_token: Option<String>,
+    shutdown_grace: Option<u64>,
 }
 
 impl Config {
@@ -45,6 +213,7 @@ impl Config {
         let mut contents = Vec::with_capacity(file_size as usize);
         file.read_to_end(&mut contents)?;
         toml::from_slice(&contents)
+            .and_then(Inner::finish)
             .map(|inner| Config(Arc::new(inner)))
             .map_err(From::from)
     }
@@ -53,28 +222,222 @@ impl Config {
         SocketAddr::new(self.0.server.host, self.0.server.port.unwrap_or(0))
     }
 
+    /// How many seconds running jobs get to finish once a shutdown starts.
+    pub fn shutdown_grace(&self) -> u64 {
+        self.0.server.shutdown_grace.unwrap_or(DEFAULT_SHUTDOWN_GRACE_SECS)
+    }
+
+    /// The bearer token that unlocks the admin endpoints, such as `/jobs`.
+    /// Without one, they're turned off.
+    pub fn admin_token(&self) -> Option<&str> {
+        self.0.server.admin_token.as_ref().map(AsRef::as_ref)
+    }
+
+    /// Jobs to run on a timer, in config order.
+    pub fn schedules(&self) -> &[Schedule] {
+        &self.0.schedule
+    }
+
+    /// The time zone reminders are read and written in, as seconds east of
+    /// UTC. Set with `timezone = "-07:00"` under `[reminders]`; it's a fixed
+    /// offset, so it doesn't change with daylight saving.
+    pub fn reminders_utc_offset(&self) -> i64 {
+        self.0.reminders.as_ref()
+            .and_then(|r| r.timezone.as_ref())
+            .and_then(|tz| parse_timezone(tz))
+            .unwrap_or(0)
+    }
+
+    /// The programs the bot may run, in config order.
+    pub fn commands(&self) -> &[Command] {
+        &self.0.command
+    }
+
+    pub fn command(&self, name: &str) -> Option<&Command> {
+        self.0.command.iter().find(|c| c.name == name)
+    }
+
+    /// The settings for `repo` on `host`, if it has any.
+    pub fn repo(&self, host: &str, repo: &str) -> Option<&Repo> {
+        let first = self.0.github.first().map(|g| g.name()).unwrap_or("github");
+        self.0.repo.iter().find(|r| {
+            r.name == repo && r.host.as_ref().map(AsRef::as_ref).unwrap_or(first) == host
+        })
+    }
+
+    /// What `name` is short for, like `ship` for `deploy prod`.
+    pub fn alias(&self, name: &str) -> Option<&str> {
+        self.0.aliases.get(name).map(String::as_str)
+    }
+
+    /// Every alias's name, in order.
+    pub fn alias_names(&self) -> Vec<&str> {
+        self.0.aliases.keys().map(String::as_str).collect()
+    }
+
+    /// Every configured GitHub host, in config order.
+    pub fn github_hosts(&self) -> &[Github] {
+        &self.0.github
+    }
+
+    pub fn github_host(&self, name: &str) -> Option<&Github> {
+        self.0.github.iter().find(|g| g.name() == name)
+    }
+
+    /// The host whose webhooks are delivered to `path`.
+    pub fn github_route(&self, path: &str) -> Option<&Github> {
+        self.0.github.iter().find(|g| g.route() == path)
+    }
+
+    #[deprecated(note = "use github_hosts(), this only knows the first host")]
     pub fn github_name(&self) -> &str {
-        self.0.github.as_ref().map(|g| g.username.as_ref()).unwrap_or("")
+        self.0.github.first().map(Github::username).unwrap_or("")
     }
 
+    #[deprecated(note = "use github_hosts(), this only knows the first host")]
     pub fn github_authorized(&self) -> &[String] {
-        self.0.github.as_ref().map(|g| g.authorized.as_ref()).unwrap_or(&[])
+        self.0.github.first().map(Github::authorized).unwrap_or(&[])
     }
 
+    #[deprecated(note = "use github_hosts(), this only knows the first host")]
     pub fn github_api(&self) -> &str {
-        self.0.github.as_ref()
-            .and_then(|g| g.api.as_ref().map(AsRef::as_ref))
-            .unwrap_or("https://api.github.com")
+        self.0.github.first().map(Github::api).unwrap_or(DEFAULT_GITHUB_API)
+    }
+
+    #[deprecated(note = "use github_hosts(), this only knows the first host")]
+    pub fn github_graphql(&self) -> String {
+        self.0.github.first()
+            .map(Github::graphql)
+            .unwrap_or_else(|| graphql_url(DEFAULT_GITHUB_API))
     }
 
+    #[deprecated(note = "use github_hosts(), this only knows the first host")]
     pub fn github_token(&self) -> Option<&str> {
-        self.0.github.as_ref()
-            .and_then(|g| g.token.as_ref().map(AsRef::as_ref))
+        self.0.github.first().and_then(Github::token)
     }
 
+    #[deprecated(note = "use github_hosts(), this only knows the first host")]
     pub fn github_webhook_secret(&self) -> Option<&str> {
-        self.0.github.as_ref()
-            .and_then(|g| g.webhook_secret.as_ref().map(AsRef::as_ref))
+        self.0.github.first().and_then(Github::webhook_secret)
+    }
+
+    /// How big the in-memory response cache may grow; 0 turns caching off.
+    pub fn cache_max_bytes(&self) -> usize {
+        self.0.cache.as_ref()
+            .and_then(|c| c.max_bytes)
+            .unwrap_or(DEFAULT_CACHE_BYTES)
+    }
+
+    pub fn cache_dir(&self) -> Option<&Path> {
+        self.0.cache.as_ref()
+            .and_then(|c| c.dir.as_ref().map(AsRef::as_ref))
+    }
+
+    /// How big the cache in `cache_dir` may grow.
+    pub fn cache_max_disk_bytes(&self) -> u64 {
+        self.0.cache.as_ref()
+            .and_then(|c| c.max_disk_bytes)
+            .unwrap_or(DEFAULT_CACHE_DISK_BYTES)
+    }
+
+    /// Where to persist jobs so they survive restarts. Without one, pending
+    /// jobs only live in memory.
+    pub fn queue_path(&self) -> Option<&Path> {
+        self.0.queue.as_ref()
+            .and_then(|q| q.path.as_ref().map(AsRef::as_ref))
+    }
+
+    /// Where to keep failed jobs. Without one, they're only kept in memory.
+    pub fn dead_letter_path(&self) -> Option<&Path> {
+        self.0.queue.as_ref()
+            .and_then(|q| q.dead_letter_path.as_ref().map(AsRef::as_ref))
+    }
+
+    /// How many jobs may be waiting or running before new ones are refused.
+    pub fn queue_capacity(&self) -> usize {
+        self.0.queue.as_ref()
+            .and_then(|q| q.capacity)
+            .unwrap_or(DEFAULT_QUEUE_CAPACITY)
+    }
+
+    /// How many jobs may be waiting to run later, such as reminders, before
+    /// new ones are refused. These don't count against `queue_capacity`.
+    pub fn queue_max_delayed(&self) -> usize {
+        self.0.queue.as_ref()
+            .and_then(|q| q.max_delayed)
+            .unwrap_or(DEFAULT_MAX_DELAYED)
+    }
+
+    /// How many jobs may run at the same time.
+    pub fn queue_max_running(&self) -> usize {
+        self.0.queue.as_ref()
+            .and_then(|q| q.max_running)
+            .unwrap_or(DEFAULT_MAX_RUNNING)
+    }
+
+    /// How many seconds to tell GitHub to wait when the queue is full.
+    pub fn queue_retry_after(&self) -> u64 {
+        self.0.queue.as_ref()
+            .and_then(|q| q.retry_after)
+            .unwrap_or(DEFAULT_RETRY_AFTER_SECS)
+    }
+
+    /// How many finished jobs to remember for `/jobs`.
+    pub fn queue_history(&self) -> usize {
+        self.0.queue.as_ref()
+            .and_then(|q| q.history)
+            .unwrap_or(DEFAULT_HISTORY)
+    }
+
+    /// Whether a job keeps running its remaining tasks after one fails
+    /// (`on_failure = "continue"`) or skips them (`"abort"`, the default).
+    pub fn queue_continue_on_failure(&self) -> bool {
+        self.0.queue.as_ref()
+            .and_then(|q| q.on_failure) == Some(OnFailure::Continue)
+    }
+
+    /// The proxy to reach GitHub through, if any.
+    ///
+    /// Unless `proxy_from_env = false`, `HTTPS_PROXY` is used when the config
+    /// doesn't name one.
+    pub fn http_proxy(&self) -> Option<String> {
+        let http = self.0.http.as_ref();
+        if let Some(proxy) = http.and_then(|h| h.proxy.clone()) {
+            return Some(proxy);
+        }
+        if http.and_then(|h| h.proxy_from_env) == Some(false) {
+            return None;
+        }
+        env_var(&["HTTPS_PROXY", "https_proxy"])
+            .and_then(|p| if p.is_empty() { None } else { Some(p) })
+    }
+
+    /// Hosts, or domains with a leading `.`, to connect to directly.
+    pub fn http_no_proxy(&self) -> Vec<String> {
+        let http = self.0.http.as_ref();
+        if let Some(list) = http.and_then(|h| h.no_proxy.clone()) {
+            return list;
+        }
+        if http.and_then(|h| h.proxy_from_env) == Some(false) {
+            return Vec::new();
+        }
+        env_var(&["NO_PROXY", "no_proxy"])
+            .map(|list| list.split(',').map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect())
+            .unwrap_or_else(Vec::new)
+    }
+
+    /// PEM bundles of extra CAs to trust, besides the system roots.
+    pub fn http_ca_files(&self) -> &[PathBuf] {
+        self.0.http.as_ref().map(|h| h.ca_files.as_ref()).unwrap_or(&[])
+    }
+
+    /// A PKCS #12 client certificate and its password.
+    pub fn http_client_cert(&self) -> Option<(&Path, &str)> {
+        self.0.http.as_ref().and_then(|h| {
+            h.client_cert.as_ref().map(|path| {
+                (path.as_ref(), h.client_cert_password.as_ref().map(AsRef::as_ref).unwrap_or(""))
+            })
+        })
     }
 }
 
@@ -83,10 +446,303 @@ impl FromStr for Config {
 
     fn from_str(s: &str) -> Result<Config, Self::Err> {
         toml::from_str(s)
+            .and_then(Inner::finish)
             .map(|inner| Config(Arc::new(inner)))
     }
 }
 
+impl Inner {
+    // fill in the defaults that depend on other hosts, make sure no two
+    // hosts or commands can be confused with each other, check the settings
+    // that only take a few values, and parse schedules
+    fn finish(mut self) -> Result<Inner, toml::de::Error> {
+        for i in 0..self.github.len() {
+            let name = self.github[i].name.clone().unwrap_or_else(|| {
+                match self.github[i].api {
+                    Some(ref api) => hostname(api).to_string(),
+                    None => "github".to_string(),
+                }
+            });
+            let route = self.github[i].route.clone().unwrap_or_else(|| {
+                if i == 0 {
+                    "/github".to_string()
+                } else {
+                    format!("/github/{}", name)
+                }
+            });
+            if self.github[..i].iter().any(|g| g.name() == name) {
+                return Err(toml::de::Error::custom(format!("duplicate github host name {:?}", name)));
+            }
+            if self.github[..i].iter().any(|g| g.route() == route) {
+                return Err(toml::de::Error::custom(format!("duplicate github webhook route {:?}", route)));
+            }
+            self.github[i].name = Some(name);
+            self.github[i].route = Some(route);
+        }
+        if self.queue.as_ref().and_then(|q| q.max_running) == Some(0) {
+            return Err(toml::de::Error::custom("queue.max_running must be at least 1"));
+        }
+        if let Some(tz) = self.reminders.as_ref().and_then(|r| r.timezone.as_ref()) {
+            if parse_timezone(tz).is_none() {
+                return Err(toml::de::Error::custom(format!(
+                    "reminders.timezone must be \"UTC\" or a fixed offset like \"-07:00\", not {:?}; \
+                     named zones and daylight saving aren't supported", tz)));
+            }
+        }
+        for (i, command) in self.command.iter().enumerate() {
+            if self.command[..i].iter().any(|c| c.name == command.name) {
+                return Err(toml::de::Error::custom(format!("duplicate command name {:?}", command.name)));
+            }
+            if command.program.is_empty() {
+                return Err(toml::de::Error::custom(format!("command {:?} needs a program", command.name)));
+            }
+        }
+        for (i, repo) in self.repo.iter().enumerate() {
+            if self.repo[..i].iter().any(|r| r.name == repo.name && r.host == repo.host) {
+                return Err(toml::de::Error::custom(format!("duplicate repo {:?}", repo.name)));
+            }
+            if let Some(ref host) = repo.host {
+                if !self.github.iter().any(|g| g.name() == host) {
+                    return Err(toml::de::Error::custom(format!(
+                        "repo {:?} names unknown github host {:?}", repo.name, host)));
+                }
+            }
+            if !repo.quiet.is_empty() && !repo.reactions {
+                return Err(toml::de::Error::custom(format!(
+                    "repo {:?} can only answer quietly with reactions = true", repo.name)));
+            }
+        }
+        for (name, command) in &self.aliases {
+            if name.is_empty() || name.contains(char::is_whitespace) {
+                return Err(toml::de::Error::custom(format!("alias {:?} has to be one word", name)));
+            }
+            let mut words = command.split_whitespace();
+            let first = match words.next() {
+                Some(first) => first,
+                None => return Err(toml::de::Error::custom(format!("alias {:?} is empty", name))),
+            };
+            if self.aliases.contains_key(first) {
+                return Err(toml::de::Error::custom(format!(
+                    "alias {:?} can't stand for another alias, {:?}", name, first)));
+            }
+            match commands::find(first) {
+                None => return Err(toml::de::Error::custom(format!(
+                    "alias {:?} stands for {:?}, which isn't a command", name, first))),
+                // it'd never be understood with anything after it
+                Some(found) if !found.args && words.next().is_some() => {
+                    return Err(toml::de::Error::custom(format!(
+                        "alias {:?} gives {:?} arguments, but it takes none", name, first)));
+                },
+                Some(_) => {},
+            }
+        }
+        for i in 0..self.schedule.len() {
+            let when = {
+                let schedule = &self.schedule[i];
+                if self.schedule[..i].iter().any(|s| s.name == schedule.name) {
+                    return Err(toml::de::Error::custom(format!("duplicate schedule name {:?}", schedule.name)));
+                }
+                if let Some(ref host) = schedule.host {
+                    if !self.github.iter().any(|g| g.name() == host) {
+                        return Err(toml::de::Error::custom(format!(
+                            "schedule {:?} names unknown github host {:?}", schedule.name, host)));
+                    }
+                }
+                match (schedule.cron.as_ref(), schedule.at.as_ref()) {
+                    (Some(expr), None) => {
+                        When::Cron(Cron::parse(expr).map_err(toml::de::Error::custom)?)
+                    },
+                    (None, Some(at)) => match cron::parse_timestamp(at) {
+                        Some(at) => When::At(at),
+                        None => return Err(toml::de::Error::custom(format!(
+                            "schedule {:?} has a bad time {:?}, try 2017-06-01T09:00:00Z", schedule.name, at))),
+                    },
+                    _ => return Err(toml::de::Error::custom(format!(
+                        "schedule {:?} needs exactly one of cron or at", schedule.name))),
+                }
+            };
+            self.schedule[i].when = Some(when);
+        }
+        Ok(self)
+    }
+}
+
+impl Github {
+    /// What the rest of the bot calls this host. Defaults to the api's
+    /// hostname, or `github` for github.com.
+    pub fn name(&self) -> &str {
+        self.name.as_ref().map(AsRef::as_ref).unwrap_or("github")
+    }
+
+    /// Where this host's webhooks are delivered. Defaults to `/github` for
+    /// the first host and `/github/<name>` for the rest.
+    pub fn route(&self) -> &str {
+        self.route.as_ref().map(AsRef::as_ref).unwrap_or("/github")
+    }
+
+    /// The bot's own login on this host.
+    pub fn username(&self) -> &str {
+        &self.username
+    }
+
+    pub fn authorized(&self) -> &[String] {
+        &self.authorized
+    }
+
+    /// Who may cancel anyone's jobs, not just their own.
+    pub fn admins(&self) -> &[String] {
+        &self.admins
+    }
+
+    /// Whether the bot answers mentions anywhere in a comment
+    /// (`mentions = "anywhere"`), or only at the start of a line (`"start"`,
+    /// the default).
+    pub fn mentions_anywhere(&self) -> bool {
+        self.mentions == Some(Mentions::Anywhere)
+    }
+
+    pub fn api(&self) -> &str {
+        self.api.as_ref().map(AsRef::as_ref).unwrap_or(DEFAULT_GITHUB_API)
+    }
+
+    /// The GraphQL endpoint, next to the REST one unless configured.
+    ///
+    /// GitHub Enterprise serves REST from `/api/v3` and GraphQL from
+    /// `/api/graphql`.
+    pub fn graphql(&self) -> String {
+        if let Some(ref url) = self.graphql {
+            return url.clone();
+        }
+        graphql_url(self.api())
+    }
+
+    pub fn token(&self) -> Option<&str> {
+        self.token.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn webhook_secret(&self) -> Option<&str> {
+        self.webhook_secret.as_ref().map(AsRef::as_ref)
+    }
+}
+
+impl Schedule {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn when(&self) -> &When {
+        self.when.as_ref().expect("filled in when the config was read")
+    }
+
+    /// The github host to act on; `None` means the first one.
+    pub fn host(&self) -> Option<&str> {
+        self.host.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn repo(&self) -> &str {
+        &self.repo
+    }
+
+    pub fn issue(&self) -> u64 {
+        self.issue
+    }
+
+    /// What to post on the issue each time it runs.
+    pub fn comment(&self) -> &str {
+        &self.comment
+    }
+}
+
+impl Repo {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Whether to react to commands: :eyes: when one's taken, then :rocket:
+    /// or :confused: once it's done, and :-1: for people who aren't allowed.
+    pub fn reactions(&self) -> bool {
+        self.reactions
+    }
+
+    /// Whether `command` is answered with reactions alone, no reply.
+    pub fn quiet(&self, command: &str) -> bool {
+        self.quiet.iter().any(|c| c == command)
+    }
+
+    /// Whether lines like `/deploy` are commands too, not just mentions.
+    /// Off by default, so as not to answer another bot's commands.
+    pub fn slash_commands(&self) -> bool {
+        self.slash_commands
+    }
+}
+
+impl Command {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn program(&self) -> &str {
+        &self.program
+    }
+
+    pub fn args(&self) -> &[String] {
+        &self.args
+    }
+
+    /// Where to run it; the bot's own working directory if not set.
+    pub fn dir(&self) -> Option<&Path> {
+        self.dir.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn env(&self) -> &BTreeMap<String, String> {
+        &self.env
+    }
+
+    /// Variables to copy from the bot's environment. Nothing else is, so
+    /// secrets like tokens aren't handed to the command by accident.
+    pub fn pass_env(&self) -> &[String] {
+        &self.pass_env
+    }
+
+    /// How many seconds it may run before it's killed.
+    pub fn timeout(&self) -> u64 {
+        self.timeout.unwrap_or(DEFAULT_COMMAND_TIMEOUT_SECS)
+    }
+
+    /// How many bytes of stdout and stderr, together, to keep in the job log.
+    pub fn max_output(&self) -> usize {
+        self.max_output.unwrap_or(DEFAULT_COMMAND_MAX_OUTPUT)
+    }
+}
+
+pub fn graphql_url(api: &str) -> String {
+    let api = api.trim_right_matches('/');
+    if api.ends_with("/v3") {
+        format!("{}/graphql", &api[..api.len() - "/v3".len()])
+    } else {
+        format!("{}/graphql", api)
+    }
+}
+
+fn parse_timezone(tz: &str) -> Option<i64> {
+    match tz.trim() {
+        "UTC" | "utc" | "Z" => Some(0),
+        offset => cron::parse_offset(offset),
+    }
+}
+
+fn hostname(url: &str) -> &str {
+    let rest = match url.find("://") {
+        Some(i) => &url[i + 3..],
+        None => url,
+    };
+    rest.split(|c| c == '/' || c == ':').next().unwrap_or(rest)
+}
+
+fn env_var(names: &[&str]) -> Option<String> {
+    names.iter().filter_map(|name| env::var(name).ok()).next()
+}
+
 #[derive(Debug)]
 pub struct Error {
     kind: ErrorKind,
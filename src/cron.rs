@@ -0,0 +1,603 @@
+//! Just enough calendar math for cron schedules, timestamps and reminders,
+//! all in UTC seconds since the epoch.
+
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+macro_rules! try_opt {
+    ($e:expr) => (match $e {
+        Some(v) => v,
+        None => return None,
+    })
+}
+
+const MINUTE: u64 = 60;
+const DAY: u64 = 24 * 60 * MINUTE;
+// a schedule that can't fire within this long never will, like `0 0 30 2 *`
+const MAX_SEARCH_DAYS: u64 = 4 * 366;
+
+/// A five field cron schedule: minute, hour, day of month, month, and day of
+/// week, as in `30 4 * * 1-5`. Fields take `*`, lists, ranges and steps.
+/// `@hourly`, `@daily`, `@weekly`, `@monthly` and `@yearly` work too.
+#[derive(Clone, PartialEq)]
+pub struct Cron {
+    source: String,
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    // when both days and weekdays are restricted, either may match
+    any_day: bool,
+}
+
+impl Cron {
+    pub fn parse(s: &str) -> Result<Cron, String> {
+        let expanded = match s.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            other => other,
+        };
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(format!("cron schedule {:?} needs 5 fields", s));
+        }
+        let days_restricted = fields[2] != "*";
+        let weekdays_restricted = fields[4] != "*";
+        let mut weekdays = parse_field(fields[4], 0, 7)?;
+        // both 0 and 7 are sunday
+        if weekdays & (1 << 7) != 0 {
+            weekdays |= 1;
+        }
+        Ok(Cron {
+            source: s.trim().to_string(),
+            minutes: parse_field(fields[0], 0, 59)?,
+            hours: parse_field(fields[1], 0, 23)?,
+            days: parse_field(fields[2], 1, 31)?,
+            months: parse_field(fields[3], 1, 12)?,
+            weekdays: weekdays,
+            any_day: days_restricted && weekdays_restricted,
+        })
+    }
+
+    /// The first time strictly after `after` that the schedule fires.
+    pub fn next_after(&self, after: u64) -> Option<u64> {
+        let start = after - after % MINUTE + MINUTE;
+        let mut day = start / DAY;
+        let mut minute_of_day = (start % DAY) / MINUTE;
+        for _ in 0..MAX_SEARCH_DAYS {
+            if self.matches_day(day) {
+                for m in minute_of_day..24 * 60 {
+                    if bit(self.hours, m / 60) && bit(self.minutes, m % 60) {
+                        return Some(day * DAY + m * MINUTE);
+                    }
+                }
+            }
+            day += 1;
+            minute_of_day = 0;
+        }
+        None
+    }
+
+    fn matches_day(&self, day: u64) -> bool {
+        let (_, month, dom) = civil_from_days(day);
+        if !bit(self.months, month) {
+            return false;
+        }
+        let by_date = bit(self.days, dom);
+        let by_weekday = bit(self.weekdays, weekday(day));
+        if self.any_day {
+            by_date || by_weekday
+        } else {
+            by_date && by_weekday
+        }
+    }
+}
+
+impl fmt::Debug for Cron {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Cron({:?})", self.source)
+    }
+}
+
+impl fmt::Display for Cron {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+fn bit(set: u64, n: u64) -> bool {
+    set & (1 << n) != 0
+}
+
+// one bit per allowed value
+fn parse_field(field: &str, min: u64, max: u64) -> Result<u64, String> {
+    let mut set = 0;
+    for part in field.split(',') {
+        let (range, step) = match part.find('/') {
+            Some(i) => {
+                let step = part[i + 1..].parse::<u64>()
+                    .map_err(|_| format!("bad step in cron field {:?}", field))?;
+                if step == 0 {
+                    return Err(format!("zero step in cron field {:?}", field));
+                }
+                (&part[..i], step)
+            },
+            None => (part, 1),
+        };
+        let (lo, hi) = if range == "*" {
+            (min, max)
+        } else if let Some(i) = range.find('-') {
+            (parse_value(&range[..i], field)?, parse_value(&range[i + 1..], field)?)
+        } else {
+            let n = parse_value(range, field)?;
+            // `5/15` means from 5 to the end, every 15
+            (n, if step > 1 { max } else { n })
+        };
+        if lo < min || hi > max || lo > hi {
+            return Err(format!("cron field {:?} is out of range {}-{}", field, min, max));
+        }
+        let mut n = lo;
+        while n <= hi {
+            set |= 1 << n;
+            n += step;
+        }
+    }
+    Ok(set)
+}
+
+fn parse_value(s: &str, field: &str) -> Result<u64, String> {
+    s.parse().map_err(|_| format!("bad value {:?} in cron field {:?}", s, field))
+}
+
+/// Seconds since the epoch, right now.
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Parses an RFC 3339 time like `2017-06-01T09:30:00Z` or
+/// `2017-06-01T09:30:00+02:00`. Seconds may be left off.
+pub fn parse_timestamp(s: &str) -> Option<u64> {
+    let s = s.trim();
+    let (date, rest) = match s.find(|c| c == 'T' || c == 't' || c == ' ') {
+        Some(i) => (&s[..i], &s[i + 1..]),
+        None => return None,
+    };
+    let day = try_opt!(parse_date(date));
+
+    let (clock, offset) = if rest.ends_with('Z') || rest.ends_with('z') {
+        (&rest[..rest.len() - 1], 0)
+    } else {
+        match rest.rfind(|c| c == '+' || c == '-') {
+            Some(i) => (&rest[..i], try_opt!(parse_offset(&rest[i..]))),
+            // no zone at all is taken as UTC
+            None => (rest, 0),
+        }
+    };
+    let secs = try_opt!(parse_clock(clock));
+    let local = (day * DAY + secs) as i64;
+    let utc = local - offset;
+    if utc < 0 {
+        None
+    } else {
+        Some(utc as u64)
+    }
+}
+
+/// Days since the epoch for `YYYY-MM-DD`.
+pub fn parse_date(s: &str) -> Option<u64> {
+    let mut parts = s.split('-');
+    let year = try_opt!(parts.next().and_then(|p| p.parse().ok()));
+    let month = try_opt!(parts.next().and_then(|p| p.parse().ok()));
+    let day = try_opt!(parts.next().and_then(|p| p.parse().ok()));
+    if parts.next().is_some() || year < 1970 || year > 9999 || month < 1 || month > 12 || day < 1 || day > days_in_month(year, month) {
+        return None;
+    }
+    Some(days_from_civil(year, month, day))
+}
+
+/// Seconds since midnight for `HH:MM` or `HH:MM:SS`.
+pub fn parse_clock(s: &str) -> Option<u64> {
+    let parts: Vec<&str> = s.split(':').collect();
+    if parts.len() < 2 || parts.len() > 3 {
+        return None;
+    }
+    let hour: u64 = try_opt!(parts[0].parse().ok());
+    let minute: u64 = try_opt!(parts[1].parse().ok());
+    // fractions of a second aren't worth keeping
+    let second: u64 = match parts.get(2) {
+        Some(s) => try_opt!(s.split('.').next().and_then(|s| s.parse().ok())),
+        None => 0,
+    };
+    if hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+    Some(hour * 3600 + minute * 60 + second)
+}
+
+/// Seconds east of UTC for `+HH:MM` or `-HH:MM`.
+pub fn parse_offset(s: &str) -> Option<i64> {
+    let sign = match s.chars().next() {
+        Some('+') => 1,
+        Some('-') => -1,
+        _ => return None,
+    };
+    let secs = try_opt!(parse_clock(&s[1..])) as i64;
+    Some(sign * secs)
+}
+
+/// Formats seconds since the epoch as `2017-06-01 09:30 UTC`.
+pub fn format_timestamp(secs: u64) -> String {
+    format_local(secs, 0)
+}
+
+/// Formats seconds since the epoch as the clock reads `offset` seconds east
+/// of UTC, as in `2017-06-01 11:30 +02:00`.
+pub fn format_local(secs: u64, offset: i64) -> String {
+    let local = (secs as i64 + offset) as u64;
+    let (year, month, day) = civil_from_days(local / DAY);
+    let minutes = (local % DAY) / MINUTE;
+    let zone = if offset == 0 {
+        "UTC".to_string()
+    } else {
+        let sign = if offset < 0 { '-' } else { '+' };
+        let abs = offset.abs() as u64 / MINUTE;
+        format!("{}{:02}:{:02}", sign, abs / 60, abs % 60)
+    };
+    format!("{:04}-{:02}-{:02} {:02}:{:02} {}", year, month, day, minutes / 60, minutes % 60, zone)
+}
+
+/// Reads a time off the front of `text`, the way people write it in a
+/// comment: `in 2 days`, `in an hour`, `tomorrow 9am`, `friday at 17:30`,
+/// `2017-06-01 10am`, or just `3pm`. Dates without a time mean 9am, and
+/// `tonight` without one means 8pm. Clock times are read `offset` seconds
+/// east of UTC.
+///
+/// Gives back the time, which is always after `now`, and the rest of the
+/// text.
+pub fn parse_when(text: &str, now: u64, offset: i64) -> Option<(u64, String)> {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let lower: Vec<String> = words.iter()
+        .map(|w| w.trim_right_matches(',').to_lowercase())
+        .collect();
+    let local_now = (now as i64 + offset) as u64;
+    let today = local_now / DAY;
+
+    let mut i = 0;
+    if lower.get(i).map_or(false, |w| w == "on") {
+        i += 1;
+    }
+    let first = match lower.get(i) {
+        Some(word) => word.as_str(),
+        None => return None,
+    };
+
+    let local = if first == "in" {
+        let (secs, used) = try_opt!(parse_duration(&lower[i + 1..]));
+        i += 1 + used;
+        try_opt!(local_now.checked_add(secs))
+    } else {
+        // when on the day, if no time is given
+        let mut default = 9 * 3600;
+        let day = if first == "today" {
+            i += 1;
+            Some(today)
+        } else if first == "tonight" {
+            i += 1;
+            default = 20 * 3600;
+            Some(today)
+        } else if first == "tomorrow" {
+            i += 1;
+            Some(today + 1)
+        } else if let Some(date) = parse_date(first) {
+            i += 1;
+            Some(date)
+        } else {
+            let next = if first == "next" { 1 } else { 0 };
+            match lower.get(i + next).and_then(|w| parse_weekday(w)) {
+                Some(wanted) => {
+                    i += next + 1;
+                    // the next one after today, a week out if it's today
+                    let ahead = (wanted + 7 - weekday(today)) % 7;
+                    Some(today + if ahead == 0 { 7 } else { ahead })
+                },
+                None => None,
+            }
+        };
+
+        if lower.get(i).map_or(false, |w| w == "at") {
+            i += 1;
+        }
+        let clock = parse_time_of_day(&lower[i..]);
+        if let Some((_, used)) = clock {
+            i += used;
+        }
+        match (day, clock) {
+            (Some(day), Some((secs, _))) => day * DAY + secs,
+            (Some(day), None) => day * DAY + default,
+            // a bare time is the next time the clock reads that
+            (None, Some((secs, _))) => {
+                if today * DAY + secs > local_now {
+                    today * DAY + secs
+                } else {
+                    (today + 1) * DAY + secs
+                }
+            },
+            (None, None) => return None,
+        }
+    };
+
+    if local > i64::max_value() as u64 {
+        return None;
+    }
+    let at = local as i64 - offset;
+    if at <= now as i64 {
+        return None;
+    }
+    Some((at as u64, words[i..].join(" ")))
+}
+
+// `2 days`, `an hour`, `30m`, `1 week`
+fn parse_duration(words: &[String]) -> Option<(u64, usize)> {
+    let first = try_opt!(words.get(0));
+    let split = first.find(|c: char| !c.is_digit(10)).unwrap_or(first.len());
+    let (amount, unit, used) = if split > 0 && split < first.len() {
+        (&first[..split], &first[split..], 1)
+    } else {
+        (first.as_str(), try_opt!(words.get(1)).as_str(), 2)
+    };
+    let amount: u64 = match amount {
+        "a" | "an" | "one" => 1,
+        n => try_opt!(n.parse().ok()),
+    };
+    let unit = match unit.trim_right_matches('s') {
+        "m" | "min" | "minute" => MINUTE,
+        "h" | "hr" | "hour" => 60 * MINUTE,
+        "d" | "day" => DAY,
+        "w" | "wk" | "week" => 7 * DAY,
+        _ => return None,
+    };
+    amount.checked_mul(unit).map(|secs| (secs, used))
+}
+
+// `9am`, `9:30 pm`, `17:00`, `noon`; seconds since midnight
+fn parse_time_of_day(words: &[String]) -> Option<(u64, usize)> {
+    let first = try_opt!(words.get(0));
+    match first.as_str() {
+        "noon" => return Some((12 * 3600, 1)),
+        "midnight" => return Some((0, 1)),
+        _ => {},
+    }
+    let (clock, meridiem, used) = if first.ends_with("am") || first.ends_with("pm") {
+        (&first[..first.len() - 2], Some(&first[first.len() - 2..]), 1)
+    } else {
+        match words.get(1).map(|w| w.as_str()) {
+            Some(m) if m == "am" || m == "pm" => (first.as_str(), Some(m), 2),
+            _ => (first.as_str(), None, 1),
+        }
+    };
+    let (hour, minute): (u64, u64) = match clock.find(':') {
+        Some(colon) => (try_opt!(clock[..colon].parse().ok()), try_opt!(clock[colon + 1..].parse().ok())),
+        // a lone number is only a time with am or pm after it
+        None if meridiem.is_some() => (try_opt!(clock.parse().ok()), 0),
+        None => return None,
+    };
+    let hour = match meridiem {
+        Some(_) if hour < 1 || hour > 12 => return None,
+        Some("am") => hour % 12,
+        Some(_) => hour % 12 + 12,
+        None => hour,
+    };
+    if hour > 23 || minute > 59 {
+        return None;
+    }
+    Some((hour * 3600 + minute * MINUTE, used))
+}
+
+fn parse_weekday(word: &str) -> Option<u64> {
+    let days = ["sunday", "monday", "tuesday", "wednesday", "thursday", "friday", "saturday"];
+    days.iter().position(|day| word == *day || (word.len() >= 3 && day.starts_with(word)))
+        .map(|i| i as u64)
+}
+
+/// 0 is sunday.
+pub fn weekday(days: u64) -> u64 {
+    // 1970-01-01 was a thursday
+    (days + 4) % 7
+}
+
+fn is_leap(year: u64) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+fn days_in_month(year: u64, month: u64) -> u64 {
+    match month {
+        2 if is_leap(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// Howard Hinnant's days_from_civil, for dates from 1970 on
+pub fn days_from_civil(year: u64, month: u64, day: u64) -> u64 {
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y / 400;
+    let yoe = y - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+/// (year, month, day) for days since the epoch.
+pub fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    let z = days + 719468;
+    let era = z / 146097;
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = if month <= 2 { yoe + era * 400 + 1 } else { yoe + era * 400 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(year: u64, month: u64, day: u64, hour: u64, minute: u64) -> u64 {
+        days_from_civil(year, month, day) * DAY + hour * 3600 + minute * MINUTE
+    }
+
+    fn next(cron: &str, after: u64) -> Option<u64> {
+        Cron::parse(cron).unwrap().next_after(after)
+    }
+
+    fn words(s: &str) -> Vec<String> {
+        s.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn test_calendar() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(days_from_civil(2000, 2, 29), 11016);
+        assert_eq!(days_from_civil(2017, 6, 1), 17318);
+        assert_eq!(civil_from_days(17166), (2016, 12, 31));
+        assert_eq!(civil_from_days(47541), (2100, 3, 1));
+        for days in 0..60000 {
+            let (year, month, day) = civil_from_days(days);
+            assert!(day <= days_in_month(year, month), "{}-{}-{}", year, month, day);
+            assert_eq!(days_from_civil(year, month, day), days);
+        }
+
+        assert_eq!(weekday(0), 4);
+        assert_eq!(weekday(17318), 4);
+        assert_eq!(weekday(47541), 1);
+        assert_eq!(days_in_month(2016, 2), 29);
+        assert_eq!(days_in_month(2000, 2), 29);
+        assert_eq!(days_in_month(2100, 2), 28);
+        assert_eq!(days_in_month(2017, 4), 30);
+        assert_eq!(days_in_month(2017, 12), 31);
+    }
+
+    #[test]
+    fn test_next_after() {
+        // 2017-06-01 was a thursday
+        let thursday = at(2017, 6, 1, 9, 30);
+        assert_eq!(next("30 4 * * 1-5", thursday), Some(at(2017, 6, 2, 4, 30)));
+        // strictly after, so friday's run is skipped and the weekend too
+        assert_eq!(next("30 4 * * 1-5", at(2017, 6, 2, 4, 30)), Some(at(2017, 6, 5, 4, 30)));
+        assert_eq!(next("@hourly", thursday + 15), Some(at(2017, 6, 1, 10, 0)));
+        assert_eq!(next("*/15 * * * *", thursday), Some(at(2017, 6, 1, 9, 45)));
+        assert_eq!(next("5/20 9 * * *", thursday), Some(at(2017, 6, 1, 9, 45)));
+        assert_eq!(next("0 0 * * 7", thursday), Some(at(2017, 6, 4, 0, 0)));
+        assert_eq!(next("0 0 1 1 *", at(2016, 12, 31, 23, 59)), Some(at(2017, 1, 1, 0, 0)));
+        assert_eq!(next("0 0 29 2 *", thursday), Some(at(2020, 2, 29, 0, 0)));
+        assert_eq!(next("0 0 30 2 *", thursday), None);
+
+        // with both day fields restricted, either one will do
+        assert_eq!(next("0 12 13 * 5", thursday), Some(at(2017, 6, 2, 12, 0)));
+        assert_eq!(next("0 12 13 * *", thursday), Some(at(2017, 6, 13, 12, 0)));
+    }
+
+    #[test]
+    fn test_parse_cron() {
+        assert!(Cron::parse("* * * *").is_err());
+        assert!(Cron::parse("60 * * * *").is_err());
+        assert!(Cron::parse("*/0 * * * *").is_err());
+        assert!(Cron::parse("5-1 * * * *").is_err());
+        assert!(Cron::parse("0 0 0 * *").is_err());
+        assert_eq!(Cron::parse(" @daily ").unwrap().to_string(), "@daily");
+    }
+
+    #[test]
+    fn test_parse_when() {
+        // a thursday
+        let now = at(2017, 6, 1, 9, 30);
+        let cases = [
+            ("in 2 days deploy", Some((now + 2 * DAY, "deploy"))),
+            ("in an hour check ci", Some((now + 3600, "check ci"))),
+            ("in 30m", Some((now + 1800, ""))),
+            ("in 1 week, ping", Some((now + 7 * DAY, "ping"))),
+            ("tomorrow 9am standup", Some((at(2017, 6, 2, 9, 0), "standup"))),
+            ("friday at 17:30 ship it", Some((at(2017, 6, 2, 17, 30), "ship it"))),
+            ("thursday lunch", Some((at(2017, 6, 8, 9, 0), "lunch"))),
+            ("next mon", Some((at(2017, 6, 5, 9, 0), ""))),
+            ("on 2017-07-04 fireworks", Some((at(2017, 7, 4, 9, 0), "fireworks"))),
+            ("2017-06-01 10am", Some((at(2017, 6, 1, 10, 0), ""))),
+            ("3pm", Some((at(2017, 6, 1, 15, 0), ""))),
+            ("9am", Some((at(2017, 6, 2, 9, 0), ""))),
+            ("today 8am", None),
+            ("tonight check the graphs", Some((at(2017, 6, 1, 20, 0), "check the graphs"))),
+            ("tonight at 11pm", Some((at(2017, 6, 1, 23, 0), ""))),
+            ("2017-05-31", None),
+            ("9999999999-01-01", None),
+            ("in 999999999999999999 weeks", None),
+            ("in 18446744073709551615 m", None),
+            ("in 307445734561825860 m", None),
+            ("hello", None),
+            ("", None),
+        ];
+        for &(text, expected) in cases.iter() {
+            let expected = expected.map(|(at, rest)| (at, rest.to_string()));
+            assert_eq!(parse_when(text, now, 0), expected, "{:?}", text);
+        }
+
+        // clock times are local, two hours east of UTC here
+        assert_eq!(parse_when("tomorrow 9am", now, 7200), Some((at(2017, 6, 2, 7, 0), "".to_string())));
+        assert_eq!(parse_when("in 1h", now, 7200), Some((now + 3600, "".to_string())));
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        let cases = [
+            ("2 days", Some((2 * DAY, 2))),
+            ("an hour", Some((3600, 2))),
+            ("one min", Some((60, 2))),
+            ("5 mins", Some((300, 2))),
+            ("30m later", Some((1800, 1))),
+            ("1 week", Some((7 * DAY, 2))),
+            ("3wks", Some((21 * DAY, 1))),
+            ("2 fortnights", None),
+            ("days", None),
+            ("999999999999999999 weeks", None),
+            ("", None),
+        ];
+        for &(text, expected) in cases.iter() {
+            assert_eq!(parse_duration(&words(text)), expected, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn test_parse_time_of_day() {
+        let cases = [
+            ("9am", Some((9 * 3600, 1))),
+            ("9 am", Some((9 * 3600, 2))),
+            ("9:30pm", Some((21 * 3600 + 1800, 1))),
+            ("9:30 pm", Some((21 * 3600 + 1800, 2))),
+            ("17:00", Some((17 * 3600, 1))),
+            ("noon", Some((12 * 3600, 1))),
+            ("midnight", Some((0, 1))),
+            ("12am", Some((0, 1))),
+            ("12pm", Some((12 * 3600, 1))),
+            ("13pm", None),
+            ("0am", None),
+            ("24:00", None),
+            ("9:60", None),
+            ("9", None),
+            ("", None),
+        ];
+        for &(text, expected) in cases.iter() {
+            assert_eq!(parse_time_of_day(&words(text)), expected, "{:?}", text);
+        }
+    }
+}
@@ -1,16 +1,26 @@
+extern crate base64;
 extern crate futures;
 extern crate hmacsha1;
 extern crate hyper;
-extern crate hyper_tls;
+extern crate libc;
 #[macro_use] extern crate log;
+extern crate native_tls;
+extern crate rand;
 extern crate serde;
 #[macro_use] extern crate serde_derive;
 #[macro_use] extern crate serde_json;
 extern crate tokio_core;
+#[macro_use] extern crate tokio_io;
+extern crate tokio_signal;
+extern crate tokio_tls;
 extern crate toml;
 
 mod bot;
+mod commands;
 mod config;
+mod cron;
 
+pub use self::bot::client;
+pub use self::bot::work;
 pub use self::config::Config;
-pub use self::bot::FxaBot;
+pub use self::bot::{FxaBot, Shutdown};
@@ -1,22 +1,41 @@
 extern crate fxabot;
 extern crate env_logger;
+extern crate futures;
+extern crate hyper;
+extern crate tokio_core;
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use futures::{Future, Stream};
+use hyper::{Client, Method};
+use hyper::client::Request;
+use tokio_core::reactor::Core;
 
 use fxabot::{FxaBot, Config};
 
 fn main() {
     env_logger::init().unwrap();
 
-    let arg = match ::std::env::args().nth(1) {
-        Some(s) => s,
+    let args: Vec<String> = ::std::env::args().collect();
+    let arg = match args.get(1) {
+        Some(s) => s.clone(),
         None => {
-            println!("Usage: {} <path>", ::std::env::args().next().unwrap());
+            usage(&args[0]);
             return;
         }
     };
 
     println!("boop: using config file {:?}", arg);
 
-    match run(arg) {
+    let result = match args.get(2).map(|s| s.as_str()) {
+        None => run(arg),
+        Some("dead-letters") => dead_letters(arg, &args[3..]),
+        Some(_) => {
+            usage(&args[0]);
+            Err(())
+        }
+    };
+    match result {
         Ok(_) => {},
         Err(e) => {
             println!("beep! error: {:?}", e);
@@ -25,8 +44,75 @@ fn main() {
     }
 }
 
+fn usage(me: &str) {
+    println!("Usage: {} <path>", me);
+    println!("       {} <path> dead-letters list", me);
+    println!("       {} <path> dead-letters (show | replay | discard) <id>", me);
+}
+
 fn run(path: String) -> Result<(), ()> {
     let config = Config::parse_file(path).expect("foo");
     let bot = FxaBot::new(config)?;
     bot.run()
 }
+
+// Dead letters live in the running bot, so ask it over the admin api.
+fn dead_letters(path: String, args: &[String]) -> Result<(), ()> {
+    let config = Config::parse_file(path).expect("foo");
+    let (method, route) = match (args.get(0).map(|s| s.as_str()), args.get(1)) {
+        (Some("list"), None) => (Method::Get, "/dead-letters".to_string()),
+        (Some("show"), Some(id)) => (Method::Get, format!("/dead-letters/{}", id)),
+        (Some("replay"), Some(id)) => (Method::Post, format!("/dead-letters/{}/replay", id)),
+        (Some("discard"), Some(id)) => (Method::Delete, format!("/dead-letters/{}", id)),
+        _ => {
+            println!("beep! try `dead-letters list` or `dead-letters show <id>`");
+            return Err(());
+        }
+    };
+    let token = match config.admin_token() {
+        Some(token) => token.to_string(),
+        None => {
+            println!("beep! the config needs an admin_token under [server]");
+            return Err(());
+        }
+    };
+    let addr = config.server_addr();
+    if addr.port() == 0 {
+        println!("beep! the config needs a port under [server] to find the bot");
+        return Err(());
+    }
+    // a bot listening everywhere can be reached locally
+    let ip = match addr.ip() {
+        IpAddr::V4(ip) if ip.is_unspecified() => IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+        IpAddr::V6(ip) if ip.is_unspecified() => IpAddr::V6(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1)),
+        ip => ip,
+    };
+    let addr = SocketAddr::new(ip, addr.port());
+
+    let mut core = Core::new().unwrap();
+    let client = Client::new(&core.handle());
+    let mut req = Request::new(method, format!("http://{}{}", addr, route).parse().unwrap());
+    req.headers_mut().set_raw("Authorization", format!("Bearer {}", token));
+    let work = client.request(req).and_then(|res| {
+        let status = res.status().clone();
+        res.body().fold(Vec::new(), |mut body, chunk| {
+            body.extend_from_slice(&chunk);
+            Ok::<_, hyper::Error>(body)
+        }).map(move |body| (status, body))
+    });
+    match core.run(work) {
+        Ok((status, body)) => {
+            println!("{}", String::from_utf8_lossy(&body));
+            if status.is_success() {
+                Ok(())
+            } else {
+                println!("beep! the bot said {}", status);
+                Err(())
+            }
+        },
+        Err(e) => {
+            println!("beep! couldn't reach the bot at {}: {}", addr, e);
+            Err(())
+        }
+    }
+}
@@ -0,0 +1,71 @@
+extern crate fxabot;
+
+use std::env;
+use std::fs;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use fxabot::client::{DiskStore, Entry, MemoryStore, Store};
+
+fn entry(body: &str) -> Entry {
+    Entry {
+        etag: Some("\"abc\"".to_string()),
+        last_modified: None,
+        link: None,
+        body: body.as_bytes().to_vec(),
+    }
+}
+
+fn body(store: &Store, key: &str) -> Option<String> {
+    store.get(key).map(|entry| String::from_utf8(entry.body).unwrap())
+}
+
+#[test]
+fn test_memory_store_evicts_least_recently_used() {
+    // room for two of these, etag and all
+    let store = MemoryStore::new(2 * entry("0123456789").body.len() + 2 * 5);
+    store.put("a", entry("0123456789"));
+    store.put("b", entry("0123456789"));
+    // using a makes b the oldest
+    assert!(store.get("a").is_some());
+    store.put("c", entry("0123456789"));
+    assert_eq!(body(&store, "b"), None);
+    assert!(store.get("a").is_some());
+    assert!(store.get("c").is_some());
+
+    // replacing doesn't count twice
+    store.put("c", entry("9876543210"));
+    assert_eq!(body(&store, "c"), Some("9876543210".to_string()));
+    assert!(store.get("a").is_some());
+
+    // too big to keep at all, and doesn't push anything out
+    store.put("d", entry(&"x".repeat(100)));
+    assert_eq!(body(&store, "d"), None);
+    assert!(store.get("a").is_some());
+}
+
+#[test]
+fn test_disk_store_is_bounded() {
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().subsec_nanos();
+    let dir = env::temp_dir().join(format!("fxabot-cache-test-{}", nanos));
+    let _ = fs::remove_dir_all(&dir);
+
+    let store = DiskStore::new(&dir, 1024).unwrap();
+    store.put("github https://api.github.com/a", entry("first"));
+    assert_eq!(body(&store, "github https://api.github.com/a"), Some("first".to_string()));
+    assert_eq!(body(&store, "other https://api.github.com/a"), None);
+
+    // still there for the next process
+    let store = DiskStore::new(&dir, 1024).unwrap();
+    assert_eq!(body(&store, "github https://api.github.com/a"), Some("first".to_string()));
+
+    for i in 0..20 {
+        store.put(&format!("key {}", i), entry(&"x".repeat(200)));
+    }
+    let total = fs::read_dir(&dir).unwrap()
+        .map(|dirent| dirent.unwrap().metadata().unwrap().len())
+        .sum::<u64>();
+    assert!(total <= 1024, "{} bytes on disk", total);
+    assert!(store.get("key 19").is_some());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
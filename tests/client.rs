@@ -0,0 +1,118 @@
+extern crate fxabot;
+extern crate hyper;
+
+use std::io;
+use std::time::{Duration, UNIX_EPOCH};
+
+use fxabot::client::{Category, Error, RateLimit, backoff};
+use hyper::{Method, StatusCode};
+use hyper::header::Headers;
+
+fn headers(pairs: &[(&str, &str)]) -> Headers {
+    let mut headers = Headers::new();
+    for &(name, value) in pairs {
+        headers.set_raw(name.to_string(), value.to_string());
+    }
+    headers
+}
+
+#[test]
+fn test_rate_limit_update_and_delay() {
+    let now = UNIX_EPOCH + Duration::from_secs(1000);
+    let mut limit = RateLimit::default();
+    assert_eq!(limit.delay(now), None);
+
+    limit.update(&headers(&[
+        ("X-RateLimit-Limit", "5000"),
+        ("X-RateLimit-Remaining", "1"),
+        ("X-RateLimit-Reset", "1060"),
+    ]), now);
+    assert_eq!(limit.limit(), Some(5000));
+    assert_eq!(limit.remaining(), Some(1));
+    assert!(!limit.is_exhausted());
+    assert_eq!(limit.delay(now), None);
+
+    // the last slot is taken, so the next request waits for the reset
+    limit.reserve();
+    assert!(limit.is_exhausted());
+    assert_eq!(limit.delay(now), Some(Duration::from_secs(60)));
+    assert_eq!(limit.delay(UNIX_EPOCH + Duration::from_secs(1060)), None);
+
+    // a Retry-After wins, and is forgotten by the next response
+    limit.update(&headers(&[("X-RateLimit-Remaining", "10"), ("Retry-After", "30")]), now);
+    assert_eq!(limit.retry_after(), Some(UNIX_EPOCH + Duration::from_secs(1030)));
+    assert_eq!(limit.delay(now), Some(Duration::from_secs(30)));
+    limit.update(&headers(&[]), now);
+    assert_eq!(limit.retry_after(), None);
+    assert_eq!(limit.remaining(), Some(10));
+    assert_eq!(limit.delay(now), None);
+}
+
+#[test]
+fn test_backoff() {
+    for _ in 0..20 {
+        let first = backoff(0);
+        assert!(first >= Duration::from_millis(250) && first <= Duration::from_millis(500), "{:?}", first);
+        let third = backoff(2);
+        assert!(third >= Duration::from_millis(1000) && third <= Duration::from_millis(2000), "{:?}", third);
+        // capped, however many tries there have been
+        let late = backoff(40);
+        assert!(late >= Duration::from_secs(15) && late <= Duration::from_secs(30), "{:?}", late);
+    }
+}
+
+#[test]
+fn test_error_categories() {
+    let cases = [
+        (StatusCode::Unauthorized, r#"{"message":"Bad credentials"}"#, Category::Auth),
+        (StatusCode::Forbidden, r#"{"message":"Must have admin rights to Repository."}"#, Category::Auth),
+        (StatusCode::NotFound, r#"{"message":"Not Found"}"#, Category::NotFound),
+        (StatusCode::UnprocessableEntity, r#"{"message":"Validation Failed"}"#, Category::Validation),
+        (StatusCode::BadRequest, r#"{"message":"Problems parsing JSON"}"#, Category::Validation),
+        (StatusCode::TooManyRequests, r#"{"message":"slow down"}"#, Category::RateLimited),
+        (StatusCode::InternalServerError, "oops", Category::Retryable),
+        (StatusCode::BadGateway, "", Category::Retryable),
+        (StatusCode::Conflict, r#"{"message":"Git Repository is empty."}"#, Category::Other),
+    ];
+    for &(status, body, category) in &cases {
+        let e = Error::api(status, body.as_bytes(), false);
+        assert_eq!(e.category(), category, "{}", e);
+        assert_eq!(e.status(), Some(status));
+        assert_eq!(e.body(), Some(body));
+    }
+
+    // a 403 is only a rate limit when nothing is left of it
+    let mut limit = RateLimit::default();
+    limit.update(&headers(&[("X-RateLimit-Remaining", "0"), ("X-RateLimit-Reset", "1060")]), UNIX_EPOCH);
+    let body = br#"{"message":"API rate limit exceeded for installation ID 1."}"#;
+    assert_eq!(Error::api(StatusCode::Forbidden, body, limit.is_exhausted()).category(), Category::RateLimited);
+
+    let refused = Error::from(hyper::Error::Io(io::Error::new(io::ErrorKind::ConnectionReset, "reset")));
+    assert_eq!(refused.category(), Category::Retryable);
+    assert_eq!(refused.status(), None);
+}
+
+#[test]
+fn test_error_details() {
+    let body = br#"{
+        "message": "Validation Failed",
+        "errors": [{"resource": "Issue", "field": "title", "code": "missing_field"}],
+        "documentation_url": "https://developer.github.com/v3/issues/#create-an-issue"
+    }"#;
+    let e = Error::api(StatusCode::UnprocessableEntity, body, false)
+        .during(&Method::Post, "/repos/mozilla/fxa/issues");
+    assert_eq!(e.message(), Some("Validation Failed"));
+    assert_eq!(e.documentation_url(), Some("https://developer.github.com/v3/issues/#create-an-issue"));
+    assert_eq!(e.request(), Some("POST /repos/mozilla/fxa/issues"));
+    assert_eq!(e.field_errors().len(), 1);
+    assert_eq!(e.field_errors()[0].field.as_ref().map(AsRef::as_ref), Some("title"));
+    assert_eq!(e.to_string(), "POST /repos/mozilla/fxa/issues: 422 Unprocessable Entity: \
+        Validation Failed; title missing_field \
+        (see https://developer.github.com/v3/issues/#create-an-issue)");
+
+    // a body that isn't GitHub's JSON is kept, but has no message
+    let e = Error::api(StatusCode::BadGateway, b"<html>bad gateway</html>", false);
+    assert_eq!(e.message(), None);
+    assert_eq!(e.documentation_url(), None);
+    assert_eq!(e.body(), Some("<html>bad gateway</html>"));
+}
@@ -0,0 +1,242 @@
+extern crate fxabot;
+
+use fxabot::Config;
+
+static SERVER: &'static str = r#"
+[server]
+host = "127.0.0.1"
+"#;
+
+fn parse(extra: &str) -> Result<Config, String> {
+    format!("{}{}", SERVER, extra).parse::<Config>().map_err(|e| e.to_string())
+}
+
+#[test]
+fn test_schedule_cron() {
+    parse(r#"
+        [[schedule]]
+        name = "nightly"
+        cron = "30 2 * * 1-5"
+        repo = "mozilla/fxa"
+        issue = 1
+        comment = "good night"
+    "#).unwrap();
+}
+
+#[test]
+fn test_schedule_at() {
+    parse(r#"
+        [[schedule]]
+        name = "launch"
+        at = "2017-06-01T09:00:00+02:00"
+        repo = "mozilla/fxa"
+        issue = 1
+        comment = "liftoff"
+    "#).unwrap();
+}
+
+#[test]
+fn test_schedule_needs_cron_or_at() {
+    let err = parse(r#"
+        [[schedule]]
+        name = "never"
+        repo = "mozilla/fxa"
+        issue = 1
+        comment = "hello?"
+    "#).unwrap_err();
+    assert!(err.contains("exactly one of cron or at"), "{}", err);
+}
+
+#[test]
+fn test_schedule_bad_cron() {
+    let err = parse(r#"
+        [[schedule]]
+        name = "broken"
+        cron = "61 * * * *"
+        repo = "mozilla/fxa"
+        issue = 1
+        comment = "tick"
+    "#).unwrap_err();
+    assert!(err.contains("out of range"), "{}", err);
+}
+
+#[test]
+fn test_reminders_timezone() {
+    parse(r#"
+        [reminders]
+        timezone = "-07:00"
+    "#).unwrap();
+
+    let err = parse(r#"
+        [reminders]
+        timezone = "America/Los_Angeles"
+    "#).unwrap_err();
+    assert!(err.contains("reminders.timezone"), "{}", err);
+    assert!(err.contains("daylight saving"), "{}", err);
+}
+
+#[test]
+fn test_repo_quiet_needs_reactions() {
+    let err = parse(r#"
+        [[repo]]
+        name = "mozilla/fxa"
+        quiet = ["ping"]
+    "#).unwrap_err();
+    assert!(err.contains("reactions = true"), "{}", err);
+
+    let config = parse(r#"
+        [[repo]]
+        name = "mozilla/fxa"
+        reactions = true
+        quiet = ["ping"]
+    "#).unwrap();
+    let repo = config.repo("github", "mozilla/fxa").unwrap();
+    assert!(repo.reactions());
+    assert!(repo.quiet("ping"));
+    assert!(!repo.quiet("deploy"));
+    assert!(config.repo("github", "mozilla/other").is_none());
+}
+
+#[test]
+fn test_aliases() {
+    let config = parse(r#"
+        [aliases]
+        ship = "deploy prod"
+        "r+" = "remind me"
+    "#).unwrap();
+    assert_eq!(config.alias("ship"), Some("deploy prod"));
+    assert_eq!(config.alias("r+"), Some("remind me"));
+    assert_eq!(config.alias("deploy"), None);
+    assert_eq!(config.alias_names(), vec!["r+", "ship"]);
+
+    let err = parse(r#"
+        [aliases]
+        ship = "deploy"
+        yolo = "ship"
+    "#).unwrap_err();
+    assert!(err.contains("another alias"), "{}", err);
+
+    let err = parse(r#"
+        [aliases]
+        "r+" = "approve"
+    "#).unwrap_err();
+    assert!(err.contains("isn't a command"), "{}", err);
+
+    // `ping` takes nothing, so `twice` would never be understood
+    let err = parse(r#"
+        [aliases]
+        pong = "ping twice"
+    "#).unwrap_err();
+    assert!(err.contains("takes none"), "{}", err);
+
+    let err = parse(r#"
+        [aliases]
+        "ship it" = "deploy"
+    "#).unwrap_err();
+    assert!(err.contains("one word"), "{}", err);
+}
+
+#[test]
+#[allow(deprecated)]
+fn test_github_hosts() {
+    let config = parse(r#"
+        [[github]]
+        username = "fxabot"
+        authorized = ["seanmonstar"]
+        token = "public"
+
+        [[github]]
+        api = "https://ghe.corp.example/api/v3"
+        username = "corpbot"
+        authorized = ["someone"]
+        webhook_secret = "shh"
+    "#).unwrap();
+
+    let hosts = config.github_hosts().iter().map(|g| (g.name(), g.route())).collect::<Vec<_>>();
+    assert_eq!(hosts, vec![("github", "/github"), ("ghe.corp.example", "/github/ghe.corp.example")]);
+    assert_eq!(config.github_route("/github").unwrap().username(), "fxabot");
+    assert_eq!(config.github_route("/github/ghe.corp.example").unwrap().username(), "corpbot");
+    assert!(config.github_route("/github/other").is_none());
+    let ghe = config.github_host("ghe.corp.example").unwrap();
+    assert_eq!(ghe.graphql(), "https://ghe.corp.example/api/graphql");
+    assert_eq!(ghe.webhook_secret(), Some("shh"));
+
+    // the old single-host accessors see the first host
+    assert_eq!(config.github_name(), "fxabot");
+    assert_eq!(config.github_authorized(), &["seanmonstar".to_string()]);
+    assert_eq!(config.github_api(), "https://api.github.com");
+    assert_eq!(config.github_token(), Some("public"));
+    assert_eq!(config.github_webhook_secret(), None);
+
+    let err = parse(r#"
+        [[github]]
+        username = "a"
+        authorized = []
+
+        [[github]]
+        username = "b"
+        authorized = []
+    "#).unwrap_err();
+    assert!(err.contains("duplicate github host name"), "{}", err);
+}
+
+#[test]
+fn test_queue_max_running() {
+    let err = parse(r#"
+        [queue]
+        max_running = 0
+    "#).unwrap_err();
+    assert!(err.contains("max_running"), "{}", err);
+
+    let config = parse(r#"
+        [queue]
+        max_running = 1
+    "#).unwrap();
+    assert_eq!(config.queue_max_running(), 1);
+}
+
+#[test]
+fn test_queue_on_failure() {
+    assert!(!parse("").unwrap().queue_continue_on_failure());
+    let config = parse(r#"
+        [queue]
+        on_failure = "continue"
+    "#).unwrap();
+    assert!(config.queue_continue_on_failure());
+    let config = parse(r#"
+        [queue]
+        on_failure = "abort"
+    "#).unwrap();
+    assert!(!config.queue_continue_on_failure());
+
+    let err = parse(r#"
+        [queue]
+        on_failure = "shrug"
+    "#).unwrap_err();
+    assert!(err.contains("shrug"), "{}", err);
+}
+
+#[test]
+fn test_github_mentions() {
+    let config = parse(r#"
+        [github]
+        username = "fxabot"
+        authorized = []
+    "#).unwrap();
+    assert!(!config.github_hosts()[0].mentions_anywhere());
+    let config = parse(r#"
+        [github]
+        username = "fxabot"
+        authorized = []
+        mentions = "anywhere"
+    "#).unwrap();
+    assert!(config.github_hosts()[0].mentions_anywhere());
+
+    let err = parse(r#"
+        [github]
+        username = "fxabot"
+        authorized = []
+        mentions = "everywhere"
+    "#).unwrap_err();
+    assert!(err.contains("everywhere"), "{}", err);
+}
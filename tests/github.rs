@@ -1,13 +1,18 @@
 extern crate futures;
 extern crate fxabot;
+#[macro_use] extern crate serde_derive;
+extern crate serde_json;
+extern crate tokio_core;
 
 use std::thread;
 
 use futures::Future;
 use futures::sync::oneshot;
 use fxabot::FxaBot;
+use fxabot::client::{Category, Client};
+use tokio_core::reactor::Core;
 
-use self::utils::request;
+use self::utils::{eventually, request, spawn_bot, spawn_github};
 mod utils;
 
 
@@ -19,15 +24,7 @@ port = 0
 
 #[test]
 fn test_smoke() {
-    let (_tx, rx) = oneshot::channel();
-    let (addr_tx, addr_rx) = oneshot::channel();
-    thread::spawn(move || {
-        let mut bot = FxaBot::new(TEST_CONFIG.parse().unwrap()).unwrap();
-        addr_tx.send(bot.addr().clone()).unwrap();
-        bot.run_until(rx.then(|_| Ok(()))).unwrap();
-    });
-
-    let addr = addr_rx.wait().unwrap();
+    let (addr, _bot) = spawn_bot(TEST_CONFIG);
 
     let res = request(&addr)
         .get("/")
@@ -40,3 +37,482 @@ fn test_smoke() {
         .response();
     assert_eq!(res.code(), 404);
 }
+
+#[test]
+fn test_queue_depth() {
+    let (addr, _bot) = spawn_bot(TEST_CONFIG);
+
+    let res = request(&addr)
+        .get("/queue")
+        .response();
+    assert_eq!(res.code(), 200);
+    assert_eq!(res.body(), r#"{"capacity":256,"delayed":0,"depth":0,"max_delayed":1024,"running":0,"waiting":0}"#);
+}
+
+#[test]
+fn test_jobs_need_admin_token() {
+    let (addr, _bot) = spawn_bot(r#"
+        [server]
+        host = "127.0.0.1"
+        port = 0
+        admin_token = "sekrit"
+    "#);
+
+    let res = request(&addr)
+        .get("/jobs")
+        .response();
+    assert_eq!(res.code(), 401);
+
+    let res = request(&addr)
+        .get("/jobs")
+        .header("Authorization", "Bearer wrong")
+        .response();
+    assert_eq!(res.code(), 401);
+
+    let res = request(&addr)
+        .get("/jobs")
+        .header("Authorization", "Bearer sekrit")
+        .response();
+    assert_eq!(res.code(), 200);
+    assert_eq!(res.body(), "[]");
+
+    let res = request(&addr)
+        .get("/jobs/1")
+        .header("Authorization", "Bearer sekrit")
+        .response();
+    assert_eq!(res.code(), 404);
+
+    let res = request(&addr)
+        .delete("/jobs/1")
+        .response();
+    assert_eq!(res.code(), 401);
+
+    let res = request(&addr)
+        .delete("/jobs/1")
+        .header("Authorization", "Bearer sekrit")
+        .response();
+    assert_eq!(res.code(), 404);
+
+    let res = request(&addr)
+        .get("/dead-letters")
+        .header("Authorization", "Bearer sekrit")
+        .response();
+    assert_eq!(res.code(), 200);
+    assert_eq!(res.body(), "[]");
+
+    let res = request(&addr)
+        .post("/dead-letters/1/replay")
+        .response();
+    assert_eq!(res.code(), 401);
+
+    let res = request(&addr)
+        .post("/dead-letters/1/replay")
+        .header("Authorization", "Bearer sekrit")
+        .response();
+    assert_eq!(res.code(), 404);
+}
+
+#[test]
+fn test_shutdown() {
+    let (handle_tx, handle_rx) = oneshot::channel();
+    let bot = thread::spawn(move || {
+        let bot = FxaBot::new(TEST_CONFIG.parse().unwrap()).unwrap();
+        handle_tx.send((bot.addr().clone(), bot.shutdown_handle())).unwrap();
+        bot.run()
+    });
+
+    let (addr, shutdown) = handle_rx.wait().unwrap();
+
+    let res = request(&addr)
+        .get("/")
+        .response();
+    assert_eq!(res.code(), 200);
+
+    shutdown.shutdown();
+    assert_eq!(bot.join().unwrap(), Ok(()));
+}
+
+fn comment_event(body: &str) -> String {
+    comment_from("seanmonstar", body)
+}
+
+fn comment_from(login: &str, body: &str) -> String {
+    format!(r#"{{
+        "action": "created",
+        "comment": {{ "id": 1, "body": {:?}, "user": {{ "login": {:?} }} }},
+        "issue": {{ "number": 1, "title": "deploy it" }},
+        "repository": {{ "full_name": "mozilla/fxa" }},
+        "sender": {{ "login": {:?} }}
+    }}"#, body, login, login)
+}
+
+#[test]
+fn test_mentions_outside_markdown_code_and_quotes() {
+    let (addr, _bot) = spawn_bot(r#"
+        [server]
+        host = "127.0.0.1"
+        port = 0
+        admin_token = "sekrit"
+
+        [github]
+        username = "fxabot"
+        authorized = ["seanmonstar"]
+    "#);
+
+    let ignored = [
+        "```\n@fxabot ping\n```",
+        "> @fxabot ping",
+        "> they said\n@fxabot ping",
+        "<!-- @fxabot ping -->",
+        "use `@fxabot ping` to check",
+        "    @fxabot ping",
+        "mail me@fxabot ping",
+        "thanks @fxabot ping",
+    ];
+    let answered = [
+        "@fxabot ping",
+        "@FxaBot: ping",
+        "looks good\n\n@fxabot, ping",
+    ];
+    for body in ignored.iter().chain(answered.iter()) {
+        let res = request(&addr)
+            .post("/github")
+            .header("X-Github-Event", "issue_comment")
+            .with_body(&comment_event(body))
+            .response();
+        assert_eq!(res.code(), 200, "{:?}", body);
+    }
+
+    let res = request(&addr)
+        .get("/jobs")
+        .header("Authorization", "Bearer sekrit")
+        .response();
+    assert_eq!(res.body().matches("\"queued_at\"").count(), answered.len());
+}
+
+#[test]
+fn test_many_commands_make_one_job() {
+    let (addr, _bot) = spawn_bot(r#"
+        [server]
+        host = "127.0.0.1"
+        port = 0
+        admin_token = "sekrit"
+
+        [github]
+        username = "fxabot"
+        authorized = ["seanmonstar"]
+    "#);
+
+    let res = request(&addr)
+        .post("/github")
+        .header("X-Github-Event", "issue_comment")
+        .with_body(&comment_event("@fxabot ping\n@fxabot reminders\n@fxabot cancel"))
+        .response();
+    assert_eq!(res.code(), 200);
+
+    let res = request(&addr)
+        .get("/jobs")
+        .header("Authorization", "Bearer sekrit")
+        .response();
+    let body = res.body();
+    assert_eq!(body.matches("\"queued_at\"").count(), 1, "{}", body);
+    assert_eq!(body.matches("\"task\"").count(), 1, "{}", body);
+}
+
+#[test]
+fn test_slash_commands_and_aliases() {
+    let (addr, _bot) = spawn_bot(r#"
+        [server]
+        host = "127.0.0.1"
+        port = 0
+        admin_token = "sekrit"
+
+        [github]
+        username = "fxabot"
+        authorized = ["seanmonstar"]
+
+        [[repo]]
+        name = "mozilla/fxa"
+        slash_commands = true
+
+        [aliases]
+        hi = "ping"
+    "#);
+
+    // `/frobnicate` is probably another bot's, so it's left alone
+    for body in &["/frobnicate", "/hi", "@fxabot hi", "/ping"] {
+        let res = request(&addr)
+            .post("/github")
+            .header("X-Github-Event", "issue_comment")
+            .with_body(&comment_event(body))
+            .response();
+        assert_eq!(res.code(), 200);
+    }
+
+    let res = request(&addr)
+        .get("/jobs")
+        .header("Authorization", "Bearer sekrit")
+        .response();
+    let body = res.body();
+    assert_eq!(body.matches("\"queued_at\"").count(), 3, "{}", body);
+}
+
+#[test]
+fn test_only_owners_and_admins_cancel() {
+    let (addr, _bot) = spawn_bot(r#"
+        [server]
+        host = "127.0.0.1"
+        port = 0
+        admin_token = "sekrit"
+
+        [github]
+        username = "fxabot"
+        authorized = ["seanmonstar", "rfk", "vladikoff"]
+        admins = ["vladikoff"]
+    "#);
+
+    let comment = |login: &str, body: &str| {
+        let res = request(&addr)
+            .post("/github")
+            .header("X-Github-Event", "issue_comment")
+            .with_body(&comment_from(login, body))
+            .response();
+        assert_eq!(res.code(), 200);
+    };
+    let state = |id: u64| {
+        let body = request(&addr)
+            .get(&format!("/jobs/{}", id))
+            .header("Authorization", "Bearer sekrit")
+            .response()
+            .body();
+        let status: serde_json::Value = serde_json::from_str(&body).unwrap();
+        status["state"].as_str().unwrap().to_string()
+    };
+
+    comment("seanmonstar", "@fxabot remind me in 2 days to ship it");
+    let body = request(&addr)
+        .get("/jobs")
+        .header("Authorization", "Bearer sekrit")
+        .response()
+        .body();
+    let jobs: serde_json::Value = serde_json::from_str(&body).unwrap();
+    let reminder = jobs.as_array().unwrap().iter()
+        .find(|job| job["description"].as_str().map_or(false, |d| d.starts_with("reminder")))
+        .and_then(|job| job["id"].as_u64())
+        .unwrap();
+
+    comment("rfk", &format!("@fxabot cancel {}", reminder));
+    assert_eq!(state(reminder), "queued");
+    comment("vladikoff", &format!("@fxabot cancel {}", reminder));
+    assert_eq!(state(reminder), "cancelled");
+}
+
+#[test]
+fn test_quiet_command_swaps_its_reactions() {
+    let github = spawn_github();
+    let (addr, _bot) = spawn_bot(&format!(r#"
+        [server]
+        host = "127.0.0.1"
+        port = 0
+
+        [http]
+        proxy_from_env = false
+
+        [github]
+        username = "fxabot"
+        authorized = ["seanmonstar"]
+        api = "http://{}"
+
+        [[repo]]
+        name = "mozilla/fxa"
+        reactions = true
+        quiet = ["ping"]
+    "#, github.addr));
+
+    let res = request(&addr)
+        .post("/github")
+        .header("X-Github-Event", "issue_comment")
+        .with_body(&comment_event("@fxabot ping"))
+        .response();
+    assert_eq!(res.code(), 200);
+
+    // the job has nothing to do, so it's done before the :eyes: lands, and
+    // the :eyes: still goes once it has
+    eventually(|| github.reactions() == vec!["rocket".to_string()]);
+    assert_eq!(github.reactions(), vec!["rocket".to_string()]);
+    assert!(github.comments().is_empty());
+}
+
+#[test]
+fn test_cancel_right_after_deploy_takes_back_the_deploy() {
+    let (addr, _bot) = spawn_bot(r#"
+        [server]
+        host = "127.0.0.1"
+        port = 0
+        admin_token = "sekrit"
+
+        [github]
+        username = "fxabot"
+        authorized = ["seanmonstar"]
+
+        [[command]]
+        name = "deploy"
+        program = "true"
+    "#);
+
+    let comment = |body: &str| {
+        let res = request(&addr)
+            .post("/github")
+            .header("X-Github-Event", "issue_comment")
+            .with_body(&comment_event(body))
+            .response();
+        assert_eq!(res.code(), 200);
+    };
+    let jobs = || {
+        let body = request(&addr)
+            .get("/jobs")
+            .header("Authorization", "Bearer sekrit")
+            .response()
+            .body();
+        serde_json::from_str::<serde_json::Value>(&body).unwrap()
+    };
+
+    let reminder = || {
+        jobs().as_array().unwrap().iter()
+            .find(|job| job["description"].as_str().map_or(false, |d| d.starts_with("reminder")))
+            .map(|job| job["state"].as_str().unwrap().to_string())
+            .unwrap()
+    };
+
+    comment("@fxabot remind me in 2 days to ship it");
+    comment("@fxabot deploy\n@fxabot cancel");
+    // the older job is left alone, and the deploy never happens
+    assert_eq!(reminder(), "queued");
+    let jobs_now = jobs();
+    assert!(!jobs_now.as_array().unwrap().iter()
+        .any(|job| job["description"].as_str().map_or(false, |d| d.starts_with("deploy"))));
+
+    // nor is a reminder what a `cancel` on its own means
+    comment("@fxabot cancel");
+    assert_eq!(reminder(), "queued");
+}
+
+#[test]
+fn test_deploy_targets() {
+    let github = spawn_github();
+    let (addr, _bot) = spawn_bot(&format!(r#"
+        [server]
+        host = "127.0.0.1"
+        port = 0
+        admin_token = "sekrit"
+
+        [http]
+        proxy_from_env = false
+
+        [github]
+        username = "fxabot"
+        authorized = ["seanmonstar"]
+        api = "http://{}"
+
+        [[command]]
+        name = "deploy"
+        program = "true"
+
+        [[command]]
+        name = "deploy-prod"
+        program = "true"
+
+        [aliases]
+        ship = "deploy prod"
+    "#, github.addr));
+
+    let comment = |body: &str| {
+        let res = request(&addr)
+            .post("/github")
+            .header("X-Github-Event", "issue_comment")
+            .with_body(&comment_event(body))
+            .response();
+        assert_eq!(res.code(), 200);
+    };
+    // what each deploy job ran
+    let deploys = || {
+        let body = request(&addr)
+            .get("/jobs")
+            .header("Authorization", "Bearer sekrit")
+            .response()
+            .body();
+        let jobs: serde_json::Value = serde_json::from_str(&body).unwrap();
+        jobs.as_array().unwrap().iter()
+            .filter(|job| job["description"].as_str().map_or(false, |d| d.starts_with("deploy")))
+            .flat_map(|job| job["tasks"].as_array().unwrap().iter())
+            .filter_map(|task| task["task"].as_str().map(|task| task.to_string()))
+            .filter(|task| task.starts_with("run "))
+            .collect::<Vec<_>>()
+    };
+
+    // a target nothing is configured for deploys nothing
+    comment("@fxabot deploy staging");
+    eventually(|| !github.comments().is_empty());
+    let comments = github.comments();
+    assert_eq!(comments.len(), 1);
+    assert!(comments[0].contains("deploy to `staging`, only to `prod`"), "{}", comments[0]);
+    assert!(deploys().is_empty());
+
+    comment("@fxabot deploy prod and staging");
+    comment("@fxabot ship");
+    assert_eq!(deploys(), vec!["run deploy-prod".to_string()]);
+}
+
+#[derive(Serialize)]
+struct RepoVars<'a> {
+    name: &'a str,
+    error: Option<&'a str>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RepoData {
+    repository: Repo,
+}
+
+#[derive(Debug, Deserialize)]
+struct Repo {
+    name: String,
+}
+
+#[test]
+fn test_graphql() {
+    let github = spawn_github();
+    let config = format!(r#"
+        [http]
+        proxy_from_env = false
+
+        [github]
+        username = "fxabot"
+        authorized = []
+        api = "http://{}"
+    "#, github.addr);
+    let mut core = Core::new().unwrap();
+    let client = Client::new(config.parse().unwrap(), &core.handle()).unwrap();
+    let query = "query($name: String!) { repository(owner: \"mozilla\", name: $name) { name } }";
+
+    let data = core.run(client.graphql::<_, RepoData>(query, &RepoVars { name: "fxa", error: None })).unwrap();
+    assert_eq!(data.repository.name, "fxa");
+
+    let errors = [
+        ("NOT_FOUND", Category::NotFound),
+        ("FORBIDDEN", Category::Auth),
+        ("RATE_LIMITED", Category::RateLimited),
+        ("INTERNAL", Category::Retryable),
+        ("ARGUMENT_ERROR", Category::Validation),
+    ];
+    for &(kind, category) in &errors {
+        let vars = RepoVars { name: "fxa", error: Some(kind) };
+        let e = core.run(client.graphql::<_, RepoData>(query, &vars)).unwrap_err();
+        assert_eq!(e.category(), category, "{}", e);
+        assert_eq!(e.graphql_errors().len(), 1);
+        assert_eq!(e.graphql_errors()[0].kind.as_ref().map(AsRef::as_ref), Some(kind));
+        assert_eq!(e.graphql_errors()[0].path, vec![serde_json::Value::String("repository".to_string())]);
+        assert_eq!(e.status(), None);
+        assert_eq!(e.to_string(), format!("POST http://{}/graphql: graphql errors: no", github.addr));
+    }
+}
@@ -0,0 +1,243 @@
+extern crate futures;
+extern crate fxabot;
+#[macro_use] extern crate serde_derive;
+extern crate serde_json;
+
+use std::env;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering, ATOMIC_BOOL_INIT, ATOMIC_USIZE_INIT};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use futures::{Async, Future, future, task};
+use fxabot::FxaBot;
+use fxabot::work::{Context, DeadLetter, DeadLetters, JobState, LogStore, Queue, Store, Task, TaskError};
+
+static TEST_CONFIG: &'static str = r#"
+[server]
+host = "127.0.0.1"
+port = 0
+"#;
+
+static COUNT: AtomicUsize = ATOMIC_USIZE_INIT;
+
+#[derive(Serialize, Deserialize)]
+struct Count {
+    by: usize,
+}
+
+impl Task for Count {
+    fn kind() -> &'static str {
+        "count"
+    }
+
+    fn describe(&self) -> String {
+        format!("count {}", self.by)
+    }
+
+    fn execute(&self, _cx: &Context) -> Box<Future<Item=(), Error=TaskError>> {
+        COUNT.fetch_add(self.by, Ordering::SeqCst);
+        Box::new(future::ok(()))
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        serde_json::to_value(self).unwrap()
+    }
+}
+
+static TALLY: AtomicUsize = ATOMIC_USIZE_INIT;
+static HEALED: AtomicBool = ATOMIC_BOOL_INIT;
+
+// counts like `Count`, but a flaky one fails until HEALED is set
+#[derive(Serialize, Deserialize)]
+struct Tally {
+    by: usize,
+    flaky: bool,
+}
+
+impl Task for Tally {
+    fn kind() -> &'static str {
+        "tally"
+    }
+
+    fn describe(&self) -> String {
+        format!("tally {}", self.by)
+    }
+
+    fn execute(&self, _cx: &Context) -> Box<Future<Item=(), Error=TaskError>> {
+        if self.flaky && !HEALED.load(Ordering::SeqCst) {
+            return Box::new(future::err(TaskError::fatal("not yet")));
+        }
+        TALLY.fetch_add(self.by, Ordering::SeqCst);
+        Box::new(future::ok(()))
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        serde_json::to_value(self).unwrap()
+    }
+}
+
+fn temp_log(name: &str) -> PathBuf {
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().subsec_nanos();
+    env::temp_dir().join(format!("fxabot-{}-{}.log", name, nanos))
+}
+
+fn lines(path: &PathBuf) -> usize {
+    BufReader::new(File::open(path).unwrap()).lines().count()
+}
+
+// runs on the bot's core until job `id` is over
+fn finish(bot: &mut FxaBot, queue: &Queue, id: u64) -> JobState {
+    bot.run_until(future::poll_fn(|| {
+        let state = queue.status(id).unwrap().state;
+        if state == JobState::Queued || state == JobState::Running {
+            task::park().unpark();
+            Ok(Async::NotReady)
+        } else {
+            Ok(Async::Ready(()))
+        }
+    })).unwrap();
+    queue.status(id).unwrap().state
+}
+
+#[test]
+fn test_resume_partly_done_job() {
+    let path = temp_log("resume");
+    let jobs = FxaBot::new(TEST_CONFIG.parse().unwrap()).unwrap().queue().clone();
+
+    let mut job = jobs.new_job();
+    job.push(&Count { by: 1 });
+    job.push(&Count { by: 10 });
+    job.push(&Count { by: 100 });
+    let id = job.id();
+    let mut done = jobs.new_job();
+    done.push(&Count { by: 1000 });
+    {
+        let mut store = LogStore::open(&path).unwrap();
+        store.recover().unwrap();
+        store.scheduled(&job).unwrap();
+        store.scheduled(&done).unwrap();
+        store.progress(id, 1).unwrap();
+        store.finished(done.id()).unwrap();
+    }
+
+    let config = format!("{}[queue]\npath = {:?}\n", TEST_CONFIG, path.to_str().unwrap());
+    let mut bot = FxaBot::new(config.parse().unwrap()).unwrap();
+    let queue = bot.queue().clone();
+    queue.register::<Count>();
+    assert_eq!(finish(&mut bot, &queue, id), JobState::Succeeded);
+    // the first task had already run, and the finished job isn't run again
+    assert_eq!(COUNT.load(Ordering::SeqCst), 110);
+    // nor does its id get handed out again
+    assert!(queue.new_job().id() > done.id());
+
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_log_store_compacts_as_it_goes() {
+    let path = temp_log("compact");
+    let jobs = FxaBot::new(TEST_CONFIG.parse().unwrap()).unwrap().queue().clone();
+
+    let mut store = LogStore::open(&path).unwrap();
+    store.recover().unwrap();
+    let mut unfinished = jobs.new_job();
+    unfinished.push(&Count { by: 1 });
+    store.scheduled(&unfinished).unwrap();
+    let mut last = 0;
+    for _ in 0..5000 {
+        let job = jobs.new_job();
+        last = job.id();
+        store.scheduled(&job).unwrap();
+        store.progress(last, 0).unwrap();
+        store.finished(last).unwrap();
+    }
+    assert!(lines(&path) < 1000, "{} lines", lines(&path));
+    drop(store);
+
+    let mut store = LogStore::open(&path).unwrap();
+    let recovered = store.recover().unwrap();
+    assert_eq!(recovered.jobs.iter().map(|job| job.id()).collect::<Vec<_>>(), vec![unfinished.id()]);
+    assert!(recovered.next_id > last);
+    assert_eq!(lines(&path), 2);
+
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_replay_skips_tasks_that_succeeded() {
+    let path = temp_log("dead");
+    let config = format!("{}[queue]\ndead_letter_path = {:?}\n", TEST_CONFIG, path.to_str().unwrap());
+    let mut bot = FxaBot::new(config.parse().unwrap()).unwrap();
+    let queue = bot.queue().clone();
+    queue.register::<Tally>();
+
+    let mut job = queue.new_job();
+    job.keep_going(true);
+    job.push(&Tally { by: 1, flaky: false });
+    job.push(&Tally { by: 10, flaky: true });
+    job.push(&Tally { by: 100, flaky: false });
+    let id = job.id();
+    queue.schedule(job).unwrap();
+    assert_eq!(finish(&mut bot, &queue, id), JobState::Failed);
+    assert_eq!(TALLY.load(Ordering::SeqCst), 101);
+
+    let letters = queue.dead_letters();
+    assert_eq!(letters.len(), 1);
+    assert_eq!(letters[0].id, id);
+    assert_eq!(letters[0].errors.len(), 1);
+    assert_eq!(letters[0].errors[0].task, 1);
+
+    // a job that only talks back isn't kept
+    let mut reply = queue.new_job();
+    reply.push(&Tally { by: 1000, flaky: true });
+    reply.disposable();
+    let reply_id = reply.id();
+    queue.schedule(reply).unwrap();
+    assert_eq!(finish(&mut bot, &queue, reply_id), JobState::Failed);
+    assert_eq!(queue.dead_letters().len(), 1);
+
+    HEALED.store(true, Ordering::SeqCst);
+    let replayed = queue.replay(id).unwrap().unwrap();
+    assert_eq!(finish(&mut bot, &queue, replayed), JobState::Succeeded);
+    // only the task that failed ran again
+    assert_eq!(TALLY.load(Ordering::SeqCst), 111);
+    assert!(queue.dead_letters().is_empty());
+    assert_eq!(queue.replay(id).unwrap(), None);
+
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_dead_letters_persist() {
+    let path = temp_log("letters");
+    let jobs = FxaBot::new(TEST_CONFIG.parse().unwrap()).unwrap().queue().clone();
+    let letter = |id| {
+        let mut job = jobs.new_job();
+        job.push(&Count { by: 1 });
+        DeadLetter {
+            id: id,
+            died_at: 1496309400,
+            errors: Vec::new(),
+            job: job,
+        }
+    };
+
+    {
+        let mut dead = DeadLetters::open(&path).unwrap();
+        dead.add(letter(1)).unwrap();
+        dead.add(letter(2)).unwrap();
+        dead.add(letter(3)).unwrap();
+        assert_eq!(dead.remove(2).unwrap().map(|letter| letter.id), Some(2));
+        assert!(dead.remove(2).unwrap().is_none());
+    }
+
+    let dead = DeadLetters::open(&path).unwrap();
+    assert_eq!(dead.list().iter().map(|letter| letter.id).collect::<Vec<_>>(), vec![1, 3]);
+    assert_eq!(dead.get(3).unwrap().died_at, 1496309400);
+    // opening compacts the log down to what's left
+    assert_eq!(lines(&path), 2);
+
+    fs::remove_file(&path).unwrap();
+}
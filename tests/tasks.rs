@@ -0,0 +1,147 @@
+extern crate futures;
+extern crate fxabot;
+#[macro_use] extern crate serde_derive;
+extern crate serde_json;
+
+use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
+
+use futures::{Async, Future, future, task};
+use fxabot::FxaBot;
+use fxabot::work::{Context, JobState, Queue, Task, TaskError};
+
+static TEST_CONFIG: &'static str = r#"
+[server]
+host = "127.0.0.1"
+port = 0
+"#;
+
+static BUMPS: AtomicUsize = ATOMIC_USIZE_INIT;
+
+#[derive(Serialize, Deserialize)]
+struct Bump {
+    by: usize,
+}
+
+impl Task for Bump {
+    fn kind() -> &'static str {
+        "bump"
+    }
+
+    fn describe(&self) -> String {
+        format!("bump by {}", self.by)
+    }
+
+    fn execute(&self, _cx: &Context) -> Box<Future<Item=(), Error=TaskError>> {
+        BUMPS.fetch_add(self.by, Ordering::SeqCst);
+        Box::new(future::ok(()))
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        serde_json::to_value(self).unwrap()
+    }
+}
+
+// runs on the bot's core until job `id` is over
+fn finish(bot: &mut FxaBot, queue: &Queue, id: u64) -> JobState {
+    bot.run_until(future::poll_fn(|| {
+        let state = queue.status(id).unwrap().state;
+        if state == JobState::Queued || state == JobState::Running {
+            task::park().unpark();
+            Ok(Async::NotReady)
+        } else {
+            Ok(Async::Ready(()))
+        }
+    })).unwrap();
+    queue.status(id).unwrap().state
+}
+
+#[test]
+fn test_custom_task() {
+    let mut bot = FxaBot::new(TEST_CONFIG.parse().unwrap()).unwrap();
+    let queue = bot.queue().clone();
+
+    // not registered yet, so the job can't run it
+    let mut job = queue.new_job();
+    job.push(&Bump { by: 1 });
+    let id = job.id();
+    queue.schedule(job).unwrap();
+    assert_eq!(finish(&mut bot, &queue, id), JobState::Failed);
+    assert_eq!(BUMPS.load(Ordering::SeqCst), 0);
+    assert_eq!(queue.dead_letters().len(), 1);
+
+    queue.register::<Bump>();
+    let mut job = queue.new_job();
+    job.push(&Bump { by: 1 });
+    job.push(&Bump { by: 2 });
+    let id = job.id();
+    queue.schedule(job).unwrap();
+    assert_eq!(finish(&mut bot, &queue, id), JobState::Succeeded);
+    assert_eq!(BUMPS.load(Ordering::SeqCst), 3);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_shell_task() {
+    use std::{env, fs, thread};
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+    use fxabot::work::Shell;
+
+    env::set_var("FXABOT_TEST_SECRET", "hunter2");
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().subsec_nanos();
+    let cleaned_up = env::temp_dir().join(format!("fxabot-cleaned-up-{}", nanos));
+    let config = format!("{}{}{:?}{}", TEST_CONFIG, r#"
+        [[command]]
+        name = "greet"
+        program = "/bin/sh"
+        args = ["-c", "echo ${FXABOT_TEST_SECRET:-no secret} $GREETING; echo oops >&2"]
+        env = { GREETING = "hello" }
+
+        [[command]]
+        name = "nap"
+        program = "/bin/sleep"
+        args = ["5"]
+        timeout = 1
+
+        [[command]]
+        name = "tidy"
+        program = "/bin/sh"
+        args = ["-c", "trap ': > \"$MARKER\"; exit 1' TERM; /bin/sleep 5 & wait"]
+        timeout = 1
+        env = { MARKER = "#, cleaned_up.to_str().unwrap(), r#" }
+    "#);
+    let config: fxabot::Config = config.parse().unwrap();
+    let mut bot = FxaBot::new(config.clone()).unwrap();
+    let queue = bot.queue().clone();
+
+    let mut job = queue.new_job();
+    job.push(&Shell::new(config.command("greet").unwrap()));
+    let id = job.id();
+    queue.schedule(job).unwrap();
+    assert_eq!(finish(&mut bot, &queue, id), JobState::Succeeded);
+    let log = queue.status(id).unwrap().log;
+    assert!(log.contains(&"no secret hello".to_string()), "{:?}", log);
+    assert!(log.contains(&"stderr: oops".to_string()), "{:?}", log);
+
+    let mut job = queue.new_job();
+    job.push(&Shell::new(config.command("nap").unwrap()));
+    let id = job.id();
+    queue.schedule(job).unwrap();
+    assert_eq!(finish(&mut bot, &queue, id), JobState::Failed);
+    let status = queue.status(id).unwrap();
+    assert_eq!(status.tasks[0].error, Some("timed out after 1s".to_string()));
+
+    // a command that's stopped gets the chance to clean up after itself
+    let mut job = queue.new_job();
+    job.push(&Shell::new(config.command("tidy").unwrap()));
+    let id = job.id();
+    queue.schedule(job).unwrap();
+    assert_eq!(finish(&mut bot, &queue, id), JobState::Failed);
+    for _ in 0..50 {
+        if cleaned_up.exists() {
+            break;
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+    assert!(cleaned_up.exists());
+    fs::remove_file(&cleaned_up).unwrap();
+}
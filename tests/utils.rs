@@ -1,13 +1,36 @@
 extern crate futures;
+extern crate fxabot;
 extern crate hyper;
+extern crate serde_json;
 extern crate tokio_core;
 
 use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
 
-use self::futures::Stream;
-use self::hyper::{Client, Uri, Method};
+use self::futures::{Future, Stream, future};
+use self::futures::sync::oneshot;
+use self::fxabot::FxaBot;
+use self::hyper::{Client, StatusCode, Uri, Method};
 use self::hyper::client::{Request as HyperRequest, Response as HyperResponse};
-use self::tokio_core::reactor::Core;
+use self::hyper::header::ContentType;
+use self::hyper::server::{Http, Request as ServerRequest, Response as ServerResponse, Service};
+use self::tokio_core::net::TcpListener;
+use self::tokio_core::reactor::{Core, Handle, Timeout};
+
+/// Runs a bot on its own thread until the returned sender is dropped.
+pub fn spawn_bot(config: &str) -> (SocketAddr, oneshot::Sender<()>) {
+    let config = config.to_string();
+    let (tx, rx) = oneshot::channel::<()>();
+    let (addr_tx, addr_rx) = oneshot::channel();
+    thread::spawn(move || {
+        let mut bot = FxaBot::new(config.parse().unwrap()).unwrap();
+        addr_tx.send(bot.addr().clone()).unwrap();
+        bot.run_until(rx.then(|_| Ok(()))).unwrap();
+    });
+    (addr_rx.wait().unwrap(), tx)
+}
 
 pub fn request(addr: &SocketAddr) -> Request {
     let core = Core::new().unwrap();
@@ -33,6 +56,26 @@ impl Request {
         self
     }
 
+    pub fn post(mut self, path: &str) -> Request {
+        self.request = Some(HyperRequest::new(Method::Post, self.uri(path)));
+        self
+    }
+
+    pub fn delete(mut self, path: &str) -> Request {
+        self.request = Some(HyperRequest::new(Method::Delete, self.uri(path)));
+        self
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Request {
+        self.request.as_mut().unwrap().headers_mut().set_raw(name.to_string(), value.to_string());
+        self
+    }
+
+    pub fn with_body(mut self, body: &str) -> Request {
+        self.request.as_mut().unwrap().set_body(body.to_string());
+        self
+    }
+
     pub fn response(self) -> Response {
         let req = self.request.unwrap();
         let mut core = self.core;
@@ -71,3 +114,152 @@ impl Response {
         String::from_utf8(body).unwrap()
     }
 }
+
+/// Just enough of GitHub's API for comments, reactions and a GraphQL query,
+/// on its own thread until it's dropped. Reactions take a moment to land, as
+/// they can for real.
+pub struct FakeGithub {
+    pub addr: SocketAddr,
+    state: Arc<Mutex<GithubState>>,
+    _stop: oneshot::Sender<()>,
+}
+
+#[derive(Default)]
+struct GithubState {
+    next_id: u64,
+    reactions: Vec<(u64, String)>,
+    comments: Vec<String>,
+}
+
+impl FakeGithub {
+    /// The reactions on the comment, oldest first.
+    pub fn reactions(&self) -> Vec<String> {
+        self.state.lock().unwrap().reactions.iter().map(|&(_, ref content)| content.clone()).collect()
+    }
+
+    /// The bodies of the comments posted.
+    pub fn comments(&self) -> Vec<String> {
+        self.state.lock().unwrap().comments.clone()
+    }
+}
+
+pub fn spawn_github() -> FakeGithub {
+    let state = Arc::new(Mutex::new(GithubState::default()));
+    let api = state.clone();
+    let (stop, stopped) = oneshot::channel::<()>();
+    let (addr_tx, addr_rx) = oneshot::channel();
+    thread::spawn(move || {
+        let mut core = Core::new().unwrap();
+        let handle = core.handle();
+        let listener = TcpListener::bind(&"127.0.0.1:0".parse().unwrap(), &handle).unwrap();
+        addr_tx.send(listener.local_addr().unwrap()).unwrap();
+        let http = Http::new();
+        let accept = listener.incoming().for_each(move |(socket, addr)| {
+            http.bind_connection(&handle, socket, addr, FakeApi {
+                state: api.clone(),
+                handle: handle.clone(),
+            });
+            Ok(())
+        });
+        let _ = core.run(accept.map_err(|_| ()).select(stopped.map_err(|_| ())));
+    });
+    FakeGithub {
+        addr: addr_rx.wait().unwrap(),
+        state: state,
+        _stop: stop,
+    }
+}
+
+struct FakeApi {
+    state: Arc<Mutex<GithubState>>,
+    handle: Handle,
+}
+
+impl Service for FakeApi {
+    type Request = ServerRequest;
+    type Response = ServerResponse;
+    type Error = self::hyper::Error;
+    type Future = Box<Future<Item=ServerResponse, Error=self::hyper::Error>>;
+
+    fn call(&self, req: ServerRequest) -> Self::Future {
+        let method = req.method().clone();
+        let path = req.path().to_string();
+        let state = self.state.clone();
+        let handle = self.handle.clone();
+        Box::new(req.body().fold(Vec::new(), |mut body, chunk| {
+            body.extend_from_slice(&chunk);
+            Ok::<_, self::hyper::Error>(body)
+        }).and_then(move |body| -> Self::Future {
+            let body: serde_json::Value = serde_json::from_slice(&body).unwrap_or(serde_json::Value::Null);
+            let text = |key: &str| body[key].as_str().unwrap_or("").to_string();
+            match method {
+                Method::Post if path.ends_with("/reactions") => {
+                    let content = text("content");
+                    let landed = Timeout::new(Duration::from_millis(300), &handle).unwrap();
+                    Box::new(landed.map_err(self::hyper::Error::from).map(move |()| {
+                        let mut state = state.lock().unwrap();
+                        state.next_id += 1;
+                        let id = state.next_id;
+                        state.reactions.push((id, content.clone()));
+                        json(StatusCode::Created, format!(
+                            r#"{{"id":{},"user":{{"login":"fxabot","id":1}},"content":{:?}}}"#, id, content))
+                    }))
+                },
+                Method::Get if path.ends_with("/reactions") => {
+                    let reactions = state.lock().unwrap().reactions.iter()
+                        .map(|&(id, ref content)| {
+                            format!(r#"{{"id":{},"user":{{"login":"fxabot","id":1}},"content":{:?}}}"#, id, content)
+                        })
+                        .collect::<Vec<_>>();
+                    ready(json(StatusCode::Ok, format!("[{}]", reactions.join(","))))
+                },
+                Method::Delete if path.contains("/reactions/") => {
+                    let id = path.rsplit('/').next().and_then(|id| id.parse::<u64>().ok());
+                    state.lock().unwrap().reactions.retain(|&(other, _)| Some(other) != id);
+                    ready(ServerResponse::new().with_status(StatusCode::NoContent))
+                },
+                Method::Post if path.ends_with("/comments") => {
+                    let mut state = state.lock().unwrap();
+                    state.next_id += 1;
+                    state.comments.push(text("body"));
+                    ready(json(StatusCode::Created, format!(
+                        r#"{{"id":{},"body":"","user":{{"login":"fxabot","id":1}},"html_url":""}}"#,
+                        state.next_id)))
+                },
+                // the repository named in the variables, or the error asked for
+                Method::Post if path == "/graphql" => {
+                    let variables = &body["variables"];
+                    let res = match variables["error"].as_str() {
+                        Some(kind) => format!(
+                            r#"{{"data":null,"errors":[{{"type":{:?},"message":"no","path":["repository"]}}]}}"#, kind),
+                        None => format!(
+                            r#"{{"data":{{"repository":{{"name":{}}}}}}}"#, variables["name"]),
+                    };
+                    ready(json(StatusCode::Ok, res))
+                },
+                _ => ready(ServerResponse::new().with_status(StatusCode::NotFound)),
+            }
+        }))
+    }
+}
+
+fn ready(res: ServerResponse) -> Box<Future<Item=ServerResponse, Error=self::hyper::Error>> {
+    Box::new(future::ok(res))
+}
+
+fn json(status: StatusCode, body: String) -> ServerResponse {
+    ServerResponse::new()
+        .with_status(status)
+        .with_header(ContentType::json())
+        .with_body(body)
+}
+
+/// Waits up to a few seconds for `done` to be true.
+pub fn eventually<F: Fn() -> bool>(done: F) {
+    for _ in 0..100 {
+        if done() {
+            return;
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+}